@@ -0,0 +1,321 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail};
+use clap::{Args, Parser, Subcommand};
+
+use stepdocs::config::{Config, ExportFormat, OutputFormat};
+use stepdocs::generator::{Snapshots, TutorialGenerator};
+use stepdocs::git::{git_ver, DiffAlgorithm, GitBackend, GitLog, GitRepository};
+use stepdocs::util::glob::Glob;
+use stepdocs::util::iter::{AsyncIterator, collect};
+
+/// Generate step by step docs from git repository
+#[derive(Debug, Parser)]
+#[command(name = "stepdocs", version, about)]
+pub struct Cli {
+	/// Path to the tutorial repository
+	#[arg(short = 'C', long = "repo", global = true, default_value = ".")]
+	pub repo: PathBuf,
+	/// Config file to use instead of `stepdocs.toml` at repository root
+	#[arg(short, long, global = true)]
+	pub config: Option<PathBuf>,
+	/// Read repository with `git` binary (`process`) or directly (`native`)
+	#[arg(long, global = true)]
+	pub backend: Option<GitBackend>,
+	/// Diff algorithm: `myers`, `patience` or `histogram`
+	#[arg(long, global = true)]
+	pub diff_algorithm: Option<DiffAlgorithm>,
+	/// Number of unchanged lines shown around each change
+	#[arg(short = 'U', long, global = true)]
+	pub context: Option<usize>,
+	#[command(subcommand)]
+	pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+	/// Generate a document with one section per step
+	Generate {
+		#[command(flatten)]
+		range: RangeArgs,
+		#[command(flatten)]
+		options: GenerateArgs,
+	},
+	/// List every step in the revision range
+	ListSteps {
+		#[command(flatten)]
+		range: RangeArgs,
+	},
+	/// Show changes introduced by a single step
+	ShowStep {
+		/// Step number (starting from 1) or commit hash prefix
+		step: String,
+		#[command(flatten)]
+		range: RangeArgs,
+	},
+	/// Export parsed steps with their commits, files and hunks for other tools
+	Export {
+		#[command(flatten)]
+		range: RangeArgs,
+		/// Export format
+		#[arg(short, long, default_value_t)]
+		format: ExportFormat,
+		/// Output file, default to stdout
+		#[arg(short, long)]
+		output: Option<PathBuf>,
+	},
+	/// Check that every step can be parsed and its patch applies on top of its parent step
+	Check {
+		#[command(flatten)]
+		range: RangeArgs,
+	},
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateArgs {
+	/// Output file, `-` for stdout; default to `output-dir` from config or stdout
+	#[arg(short, long)]
+	output: Option<PathBuf>,
+	/// Title of generated document
+	#[arg(short, long)]
+	title: Option<String>,
+	/// Output format
+	#[arg(short, long)]
+	format: Option<OutputFormat>,
+	/// Only render files matching this glob, can be repeated
+	#[arg(long)]
+	include: Vec<String>,
+	/// Never render files matching this glob, can be repeated
+	#[arg(long)]
+	exclude: Vec<String>,
+	/// Render complete file with changed lines highlighted for files matching this glob, can be repeated
+	#[arg(long)]
+	full_file: Vec<String>,
+	/// Append collapsible full source after diff of every changed file
+	#[arg(long, overrides_with = "no_full_source")]
+	full_source: bool,
+	/// Disable full source enabled in config
+	#[arg(long, overrides_with = "full_source")]
+	no_full_source: bool,
+	/// Show changed words of modified lines as `[-old-]{+new+}`
+	#[arg(long, overrides_with = "no_word_diff")]
+	word_diff: bool,
+	/// Show whole changed lines even when word diff is enabled in config
+	#[arg(long, overrides_with = "word_diff")]
+	no_word_diff: bool,
+	/// Write one html page per step next to an index page
+	#[arg(long, overrides_with = "no_split_steps")]
+	split_steps: bool,
+	/// Write a single html page even when split steps is enabled in config
+	#[arg(long, overrides_with = "split_steps")]
+	no_split_steps: bool,
+	/// Insert table of contents after title or at `<!-- toc -->` line
+	#[arg(long, overrides_with = "no_toc")]
+	toc: bool,
+	/// Disable table of contents enabled in config
+	#[arg(long, overrides_with = "toc")]
+	no_toc: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RangeArgs {
+	/// Revision range to walk such as `main`, `v1..v2` or `main~10..main`, default to all refs
+	pub range: Option<String>,
+	/// Only follow first parent of merge commits
+	#[arg(long, overrides_with = "no_first_parent")]
+	pub first_parent: bool,
+	/// Follow every parent even when first parent is enabled in config
+	#[arg(long, overrides_with = "first_parent")]
+	pub no_first_parent: bool,
+}
+
+pub async fn run(cli: Cli) -> anyhow::Result<()> {
+	let mut config = match &cli.config {
+		Some(path) => Config::load_file(path)?,
+		None => Config::load(&cli.repo)?,
+	};
+	if let Some(backend) = cli.backend {
+		config.backend = backend;
+	}
+	if let Some(algorithm) = cli.diff_algorithm {
+		config.diff_algorithm = algorithm;
+	}
+	if let Some(context) = cli.context {
+		config.context = Some(context);
+	}
+	let repo = GitRepository::open(cli.repo, config.backend)?
+		.with_diff_options(config.diff_options());
+	match cli.command {
+		Command::Generate { range, options } => {
+			range.merge(&mut config);
+			generate(&repo, config, options).await
+		}
+		Command::ListSteps { range } => {
+			range.merge(&mut config);
+			list_steps(&repo, config).await
+		}
+		Command::ShowStep { step, range } => {
+			range.merge(&mut config);
+			show_step(&repo, config, &step).await
+		}
+		Command::Export { range, format, output } => {
+			range.merge(&mut config);
+			export(&repo, config, format, output).await
+		}
+		Command::Check { range } => {
+			range.merge(&mut config);
+			check(&repo, config).await
+		}
+	}
+}
+
+impl RangeArgs {
+	fn merge(self, config: &mut Config) {
+		if let Some(range) = self.range {
+			config.range = Some(range);
+		}
+		if let Some(first_parent) = flag(self.first_parent, self.no_first_parent) {
+			config.first_parent = first_parent;
+		}
+	}
+}
+
+/// Value of `--name` and `--no-name` pair where the last one given wins, `None` keeps config
+fn flag(yes: bool, no: bool) -> Option<bool> {
+	match (yes, no) {
+		(true, _) => Some(true),
+		(_, true) => Some(false),
+		_ => None,
+	}
+}
+
+async fn steps(repo: &GitRepository, config: Config) -> anyhow::Result<Vec<GitLog>> {
+	Ok(TutorialGenerator::with_config(repo, config).steps().await?)
+}
+
+async fn generate(repo: &GitRepository, mut config: Config, options: GenerateArgs) -> anyhow::Result<()> {
+	if let Some(title) = options.title {
+		config.title = Some(title);
+	}
+	if let Some(format) = options.format {
+		config.format = format;
+	}
+	config.include.extend(options.include);
+	config.exclude.extend(options.exclude);
+	config.full_files.extend(options.full_file);
+	if let Some(full_source) = flag(options.full_source, options.no_full_source) {
+		config.full_source = full_source;
+	}
+	if let Some(word_diff) = flag(options.word_diff, options.no_word_diff) {
+		config.word_diff = word_diff;
+	}
+	if let Some(split_steps) = flag(options.split_steps, options.no_split_steps) {
+		config.split_steps = split_steps;
+	}
+	if let Some(toc) = flag(options.toc, options.no_toc) {
+		config.toc = toc;
+	}
+	let output = match options.output {
+		Some(path) if path.as_os_str() == "-" => None,
+		Some(path) => Some(path),
+		None => config.output_dir.as_ref()
+			.map(|dir| match config.format {
+				OutputFormat::Mdbook => repo.path().join(dir).join("book"),
+				format => repo.path().join(dir).join(format!("tutorial.{}", format.extension())),
+			}),
+	};
+
+	let generator = TutorialGenerator::with_config(repo, config);
+	match output {
+		Some(path) => generator.write_to(path).await?,
+		None => print!("{}", generator.generate().await?),
+	}
+	Ok(())
+}
+
+async fn export(repo: &GitRepository, config: Config, format: ExportFormat, output: Option<PathBuf>) -> anyhow::Result<()> {
+	let document = TutorialGenerator::with_config(repo, config).export(format).await?;
+	match output {
+		Some(path) if path.as_os_str() != "-" => tokio::fs::write(path, document).await?,
+		_ => println!("{document}"),
+	}
+	Ok(())
+}
+
+async fn list_steps(repo: &GitRepository, config: Config) -> anyhow::Result<()> {
+	for (no, step) in steps(repo, config).await?.iter().enumerate() {
+		println!("{:>3} {} {}", no + 1, &step.hash[..7.min(step.hash.len())], step.subject);
+	}
+	Ok(())
+}
+
+async fn show_step(repo: &GitRepository, config: Config, step: &str) -> anyhow::Result<()> {
+	let steps = steps(repo, config).await?;
+	let found = match step.parse::<usize>() {
+		Ok(no) if no > 0 && no <= steps.len() => steps.get(no - 1),
+		_ => steps.iter().find(|it| it.hash.starts_with(step)),
+	};
+	let log = found.ok_or_else(|| anyhow!("No step matching `{step}`"))?;
+	println!("commit {}", log.hash);
+	if log.is_merge() {
+		println!("Merge: {}", log.parents.join(" "));
+	}
+	println!("Author: {}", log.author);
+	println!("Date:   {}", log.author.time);
+	println!();
+	for line in log.message.lines() {
+		println!("    {line}");
+	}
+	println!();
+
+	let mut diffs = repo.show(&log.hash)?;
+	while let Some(diff) = diffs.next().await? {
+		match (&diff.old_path, &diff.new_path) {
+			(Some(old), Some(new)) if diff.is_rename() => println!("{}: {old} -> {new}", diff.kind),
+			_ => println!("{}: {}", diff.kind, diff.path()),
+		}
+		for patch in 0..diff.diffs.patches() {
+			let Some(patch) = diff.diffs.get_patch(patch) else { continue };
+			println!("{}", patch.offset);
+			for line in 0..patch.patch_lines() {
+				if let Some(line) = patch.get_line(line) {
+					println!("{line}");
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+async fn check(repo: &GitRepository, config: Config) -> anyhow::Result<()> {
+	match repo.backend() {
+		GitBackend::Process => {
+			let Some(version) = git_ver().await else {
+				bail!("Did you have `git` installed?");
+			};
+			println!("git {version}");
+		}
+		GitBackend::Native => println!("native backend, {}", repo.path().display()),
+	}
+	let full_files = config.full_files.iter().map(Glob::new).collect::<Vec<_>>();
+	let steps = steps(repo, config).await?;
+	if steps.is_empty() {
+		bail!("No steps found in {}", repo.path().display());
+	}
+	// every patch must apply on top of its first parent step and produce the recorded file
+	let mut snapshots = Snapshots::default();
+	for (no, step) in steps.iter().enumerate() {
+		let files = collect(repo.show(&step.hash)?).await?;
+		let snapshot = snapshots.apply(repo, step, &files).await?;
+		snapshot.verify(repo, &step.hash, &files).await?;
+		println!("{:>3} {} {} file(s)", no + 1, &step.hash[..7.min(step.hash.len())], files.len());
+	}
+	// a full file pattern matching nothing in the finished tutorial is likely a typo
+	let last = &steps[steps.len() - 1].hash;
+	let tree = repo.ls_tree(last).await?;
+	for glob in full_files.iter().filter(|glob| !tree.iter().any(|it| glob.matches(&it.path))) {
+		eprintln!("warning: `full-files` pattern `{}` matches no file", glob.as_str());
+	}
+	println!("{} step(s) ok", steps.len());
+	Ok(())
+}
@@ -0,0 +1,280 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::git::{DiffAlgorithm, DiffOptions, GitBackend};
+use crate::util::glob::{Glob, matches_any};
+
+/// Name of configuration file expected at repository root
+pub const CONFIG_FILE: &str = "stepdocs.toml";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+	#[error("Unable to read `{0}`: {1}")]
+	Io(PathBuf, #[source] std::io::Error),
+	#[error("Invalid config `{0}`: {1}")]
+	Parse(PathBuf, #[source] toml::de::Error),
+}
+
+/// Per-project generation settings loaded from `stepdocs.toml`
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+	/// Revision range to walk such as `main` or `v1..v2`
+	pub range: Option<String>,
+	/// Only follow first parent of merge commits so commits of merged branches are not steps
+	pub first_parent: bool,
+	/// Title of generated document
+	pub title: Option<String>,
+	/// Output directory relative to repository root
+	pub output_dir: Option<PathBuf>,
+	/// Only files matching any of these globs are rendered (all files when empty)
+	pub include: Vec<String>,
+	/// Files matching any of these globs are never rendered
+	pub exclude: Vec<String>,
+	pub format: OutputFormat,
+	/// Read repository with `git` binary (`process`) or directly (`native`)
+	pub backend: GitBackend,
+	pub diff_algorithm: DiffAlgorithm,
+	/// Number of unchanged lines around each change, 3 when not set
+	pub context: Option<usize>,
+	/// Extract changed images next to generated document and embed them
+	pub embed_images: bool,
+	/// Render complete file with changed lines highlighted instead of hunks for files matching these globs
+	pub full_files: Vec<String>,
+	/// Append collapsible block with complete file after diff of every changed file
+	pub full_source: bool,
+	/// Show changed words of modified lines as `[-old-]{+new+}` instead of whole lines
+	pub word_diff: bool,
+	/// Write one page per step next to an index page (html only)
+	pub split_steps: bool,
+	/// Table of contents linking steps and files, at `<!-- toc -->` line or after title (markdown), `:toc:` for asciidoc
+	pub toc: bool,
+	/// Overrides for specific steps
+	#[serde(rename = "step")]
+	pub steps: Vec<StepConfig>,
+	/// Branches of tutorial parts in order, every part is first-parent history of its branch since previous branch,
+	/// `range` is not used when set
+	pub branches: Vec<String>,
+	/// Parts of the document when `branches` is empty, steps are grouped by annotated tags when both are empty
+	#[serde(rename = "section")]
+	pub sections: Vec<SectionConfig>,
+}
+
+/// Settings for a single step, matched by commit hash prefix
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct StepConfig {
+	pub commit: String,
+	/// Replace commit subject in step heading
+	pub title: Option<String>,
+	/// Replace commit body as step prose
+	pub prose: Option<String>,
+	/// Leave this commit out of generated document
+	pub skip: bool,
+	pub include: Vec<String>,
+	pub exclude: Vec<String>,
+}
+
+/// Part of the document starting at a commit, matched by hash prefix
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SectionConfig {
+	pub commit: String,
+	pub title: String,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+	#[default]
+	Markdown,
+	/// Self-contained page with navigation and highlighted diffs
+	Html,
+	/// mdBook directory with `book.toml`, `SUMMARY.md` and one chapter per step
+	Mdbook,
+	/// AsciiDoc for Asciidoctor or Antora with a section per step
+	Asciidoc,
+}
+
+impl OutputFormat {
+	/// File extension of generated document, markdown for chapters of mdBook
+	pub fn extension(&self) -> &'static str {
+		match self {
+			OutputFormat::Markdown | OutputFormat::Mdbook => "md",
+			OutputFormat::Html => "html",
+			OutputFormat::Asciidoc => "adoc",
+		}
+	}
+}
+
+impl Display for OutputFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			OutputFormat::Markdown => "markdown",
+			OutputFormat::Html => "html",
+			OutputFormat::Mdbook => "mdbook",
+			OutputFormat::Asciidoc => "asciidoc",
+		})
+	}
+}
+
+impl FromStr for OutputFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"markdown" | "md" => Ok(OutputFormat::Markdown),
+			"html" | "htm" => Ok(OutputFormat::Html),
+			"mdbook" | "book" => Ok(OutputFormat::Mdbook),
+			"asciidoc" | "adoc" => Ok(OutputFormat::Asciidoc),
+			_ => Err(format!("Unknown output format `{s}`")),
+		}
+	}
+}
+
+/// Machine readable format of `export` command
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum ExportFormat {
+	/// Versioned JSON document of steps, commits, files and hunks
+	#[default]
+	Json,
+}
+
+impl Display for ExportFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			ExportFormat::Json => "json",
+		})
+	}
+}
+
+impl FromStr for ExportFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"json" => Ok(ExportFormat::Json),
+			_ => Err(format!("Unknown export format `{s}`")),
+		}
+	}
+}
+
+impl Config {
+	/// Load `stepdocs.toml` from repository root, return default config if file does not exist
+	pub fn load(repo: impl AsRef<Path>) -> Result<Self, ConfigError> {
+		let path = repo.as_ref().join(CONFIG_FILE);
+		if !path.exists() {
+			return Ok(Self::default());
+		}
+		Self::load_file(path)
+	}
+
+	pub fn load_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+		let path = path.as_ref();
+		let content = std::fs::read_to_string(path)
+			.map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+		toml::from_str(&content)
+			.map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+	}
+
+	pub fn diff_options(&self) -> DiffOptions {
+		let default = DiffOptions::default();
+		DiffOptions {
+			algorithm: self.diff_algorithm,
+			context: self.context.unwrap_or(default.context),
+		}
+	}
+
+	/// Find override for commit `hash`
+	pub fn step(&self, hash: &str) -> Option<&StepConfig> {
+		self.steps.iter()
+			.find(|it| !it.commit.is_empty() && hash.starts_with(&it.commit))
+	}
+
+	/// Find section starting at commit `hash`
+	pub fn section(&self, hash: &str) -> Option<&SectionConfig> {
+		self.sections.iter()
+			.find(|it| !it.commit.is_empty() && hash.starts_with(&it.commit))
+	}
+
+	/// Check if complete file at `path` should be rendered instead of its hunks
+	pub fn is_full_file(&self, path: &str) -> bool {
+		let globs = self.full_files.iter().map(Glob::new).collect::<Vec<_>>();
+		matches_any(&globs, path)
+	}
+
+	/// Check if file at `path` should be rendered for commit `hash`
+	pub fn is_included(&self, hash: &str, path: &str) -> bool {
+		let step = self.step(hash);
+		let include = self.include.iter()
+			.chain(step.into_iter().flat_map(|it| &it.include))
+			.map(Glob::new)
+			.collect::<Vec<_>>();
+		let exclude = self.exclude.iter()
+			.chain(step.into_iter().flat_map(|it| &it.exclude))
+			.map(Glob::new)
+			.collect::<Vec<_>>();
+		(include.is_empty() || matches_any(&include, path)) && !matches_any(&exclude, path)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::{Config, OutputFormat};
+	use crate::git::{DiffAlgorithm, DiffOptions, GitBackend};
+
+	const CONFIG: &str = r#"
+range = "main"
+title = "Hello tutorial"
+output-dir = "docs"
+exclude = ["*.lock"]
+full-files = ["src/main.rs"]
+format = "markdown"
+backend = "native"
+diff-algorithm = "patience"
+branches = ["part-1", "part-2"]
+
+[[step]]
+commit = "abc123"
+title = "Setup project"
+exclude = ["README.md"]
+
+[[step]]
+commit = "def456"
+skip = true
+
+[[section]]
+commit = "abc123"
+title = "Basics"
+"#;
+
+	#[test]
+	fn test_parse_config() {
+		let config: Config = toml::from_str(CONFIG).unwrap();
+		assert_eq!(config.range.as_deref(), Some("main"));
+		assert_eq!(config.format, OutputFormat::Markdown);
+		assert_eq!(config.backend, GitBackend::Native);
+		assert_eq!(config.diff_options(), DiffOptions { algorithm: DiffAlgorithm::Patience, context: 3 });
+		assert_eq!(config.steps.len(), 2);
+		assert_eq!(config.step("abc1234567").and_then(|it| it.title.as_deref()), Some("Setup project"));
+		assert_eq!(config.section("abc1234567").map(|it| it.title.as_str()), Some("Basics"));
+		assert_eq!(config.branches, ["part-1", "part-2"]);
+		assert!(config.step("def4567890").unwrap().skip);
+		assert!(config.step("0000000").is_none());
+	}
+
+	#[test]
+	fn test_included() {
+		let config: Config = toml::from_str(CONFIG).unwrap();
+		assert!(config.is_included("abc123", "src/main.rs"));
+		assert!(!config.is_included("abc123", "Cargo.lock"));
+		assert!(!config.is_included("abc123", "README.md"));
+		assert!(config.is_included("def456", "README.md"));
+		assert!(config.is_full_file("src/main.rs"));
+		assert!(!config.is_full_file("src/lib.rs"));
+	}
+}
@@ -0,0 +1,151 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+use thiserror::Error;
+
+use crate::config::ConfigError;
+
+pub type Result<T, E = StepdocsError> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum StepdocsError {
+	#[error(transparent)]
+	Io(#[from] io::Error),
+	#[error(transparent)]
+	Config(#[from] ConfigError),
+	#[error(transparent)]
+	Parse(#[from] ParseError),
+	#[error(transparent)]
+	Json(#[from] serde_json::Error),
+	/// Patch of a step does not apply on top of previous steps
+	#[error("Unable to apply `{file}` of commit {commit}: {source}")]
+	Apply {
+		commit: String,
+		file: String,
+		#[source]
+		source: ApplyError,
+	},
+	/// Revision or object which does not exist in repository
+	#[error("Unknown revision or object `{0}`")]
+	NotFound(String),
+	/// Malformed git object, ref, index or pack file
+	#[error("Corrupted {what}: {reason}")]
+	Corrupt {
+		what: String,
+		reason: String,
+	},
+	/// `git` exited with non-zero status
+	#[error("`git {command}` failed: {message}")]
+	Git {
+		command: String,
+		message: String,
+	},
+}
+
+impl StepdocsError {
+	pub(crate) fn corrupt(what: impl Into<String>, reason: impl Into<String>) -> Self {
+		StepdocsError::Corrupt {
+			what: what.into(),
+			reason: reason.into(),
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ParseErrorKind {
+	/// Expect `diff --git` header
+	DiffHeader,
+	/// Malformed `@@ -a,b +c,d @@` header
+	HunkHeader,
+	/// Hunk line without ` `, `+`, `-` or `\` prefix
+	HunkLine,
+	/// Number of lines in hunk does not match its header
+	HunkLength,
+	/// Stream ended in the middle of an entry
+	UnexpectedEof,
+	/// Malformed `git log` output
+	Log,
+}
+
+impl Display for ParseErrorKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			ParseErrorKind::DiffHeader => "Expect `diff --git` header",
+			ParseErrorKind::HunkHeader => "Malformed hunk header",
+			ParseErrorKind::HunkLine => "Invalid hunk line",
+			ParseErrorKind::HunkLength => "Hunk length does not match its header",
+			ParseErrorKind::UnexpectedEof => "Unexpected end of stream",
+			ParseErrorKind::Log => "Malformed log entry",
+		})
+	}
+}
+
+/// Parsing failure with location of offending text
+#[derive(Debug, Clone, Error)]
+pub struct ParseError {
+	pub kind: ParseErrorKind,
+	/// Line number in parsed stream starting from 1, 0 if unknown
+	pub line: usize,
+	pub text: String,
+	pub commit: Option<String>,
+	pub file: Option<String>,
+}
+
+impl ParseError {
+	pub fn new(kind: ParseErrorKind, text: impl Into<String>) -> Self {
+		Self {
+			kind,
+			line: 0,
+			text: text.into(),
+			commit: None,
+			file: None,
+		}
+	}
+
+	pub fn at_line(mut self, line: usize) -> Self {
+		self.line = line;
+		self
+	}
+
+	pub fn in_commit(mut self, commit: Option<impl Into<String>>) -> Self {
+		self.commit = commit.map(Into::into);
+		self
+	}
+
+	pub fn in_file(mut self, file: Option<impl Into<String>>) -> Self {
+		self.file = file.map(Into::into);
+		self
+	}
+}
+
+impl Display for ParseError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.kind)?;
+		if self.line > 0 {
+			write!(f, " at line {}", self.line)?;
+		}
+		if let Some(commit) = &self.commit {
+			write!(f, " of commit {commit}")?;
+		}
+		if let Some(file) = &self.file {
+			write!(f, " in `{file}`")?;
+		}
+		write!(f, ": {:?}", self.text)
+	}
+}
+
+/// Failure to apply a patch to file content
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum ApplyError {
+	/// Context or removed lines of hunk are not found near expected position
+	#[error("Hunk #{hunk} `{header}` does not match content near line {line}")]
+	Conflict {
+		/// Hunk number starting from 1
+		hunk: usize,
+		header: String,
+		line: u64,
+	},
+	/// Patch applied but result differ from the file recorded in commit
+	#[error("Result does not match blob {0}")]
+	Mismatch(String),
+}
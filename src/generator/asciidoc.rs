@@ -0,0 +1,298 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::config::Config;
+use crate::generator::highlight::language;
+use crate::generator::tutorial::binary_summary;
+use crate::generator::{DocumentRenderer, MarkdownBuilder, MarkdownRenderer, Part, Step, StepFile};
+use crate::git::{BinaryInfo, ChangeKind, DiffType, PatchInfo};
+
+/// Render document as AsciiDoc for Asciidoctor or Antora, every step is a section with `step-N` anchor
+/// and hunks of a file share one `[source,diff]` listing with a callout for every hunk
+pub struct AsciidocRenderer<'a> {
+	config: &'a Config,
+	doc: String,
+	/// Rendered steps changing every path, for cross-references to earlier steps
+	history: HashMap<String, Vec<usize>>,
+	/// Listing and callout list of hunks of current file
+	hunks: Option<(String, String)>,
+}
+
+impl<'a> AsciidocRenderer<'a> {
+	pub fn new(config: &'a Config) -> Self {
+		Self {
+			config,
+			doc: String::new(),
+			history: HashMap::new(),
+			hunks: None,
+		}
+	}
+
+	fn close_hunks(&mut self) {
+		if let Some((listing, callouts)) = self.hunks.take() {
+			let style = if self.config.word_diff { "[source]" } else { "[source,diff]" };
+			write_listing(&mut self.doc, style, &listing);
+			self.doc.push_str(&callouts);
+			self.doc.push('\n');
+		}
+	}
+
+	/// Latest step before `no` changing `path`
+	fn previous_step(&self, no: usize, path: &str) -> Option<usize> {
+		self.history.get(path)?
+			.iter()
+			.rev()
+			.find(|it| **it < no)
+			.copied()
+	}
+}
+
+impl DocumentRenderer for AsciidocRenderer<'_> {
+	fn begin_document(&mut self, title: Option<&str>, _steps: &[Step]) {
+		if let Some(title) = title {
+			let _ = writeln!(self.doc, "= {}", escape(title));
+		}
+		// attributes belong to document header right after title
+		if self.config.toc {
+			self.doc.push_str(":toc:\n");
+		}
+		if title.is_some() || self.config.toc {
+			self.doc.push('\n');
+		}
+	}
+
+	/// Discrete heading which is left out of table of contents, followed by intro
+	fn begin_part(&mut self, part: &Part) {
+		let Some(title) = &part.title else {
+			return;
+		};
+		let _ = write!(self.doc, "[discrete]\n== {}\n\n", escape(title));
+		if let Some(intro) = &part.intro {
+			let _ = write!(self.doc, "{intro}\n\n");
+		}
+	}
+
+	fn begin_step(&mut self, step: &Step) {
+		// only rendered steps have an anchor to refer to
+		for file in &step.files {
+			self.history.entry(file.path().to_string()).or_default().push(step.no);
+		}
+		let _ = write!(self.doc, "[[step-{no}]]\n== Step {no}: {}\n\n", escape(&step.title), no = step.no);
+	}
+
+	fn prose(&mut self, _step: &Step, text: &str) {
+		self.doc.push_str(text);
+		self.doc.push_str("\n\n");
+	}
+
+	/// File heading followed by cross-reference to step which changed it before
+	fn begin_file(&mut self, step: &Step, file: &StepFile) {
+		let diff = &file.diff;
+		let doc = &mut self.doc;
+		let _ = write!(doc, "=== {}", code(diff.path()));
+		match diff.kind {
+			ChangeKind::Added => doc.push_str(" (new file)"),
+			ChangeKind::Deleted => doc.push_str(" (deleted)"),
+			ChangeKind::Renamed | ChangeKind::Copied => {
+				let _ = write!(doc, " ({} from {})", diff.kind, code(diff.old_path.as_deref().unwrap_or_default()));
+			}
+			ChangeKind::ModeChange => {
+				let _ = write!(
+					doc,
+					" (mode changed from {} to {})",
+					code(&format!("{:o}", diff.old_mode.unwrap_or_default())),
+					code(&format!("{:o}", diff.new_mode.unwrap_or_default()))
+				);
+			}
+			ChangeKind::Modified => {}
+		}
+		doc.push_str("\n\n");
+		let previous = [diff.old_path.as_deref(), diff.new_path.as_deref()]
+			.into_iter()
+			.flatten()
+			.filter_map(|path| self.previous_step(step.no, path))
+			.max();
+		if let (Some(no), false) = (previous, diff.kind == ChangeKind::Added) {
+			let _ = write!(self.doc, "Last changed in <<step-{no}>>.\n\n");
+		}
+	}
+
+	fn binary(&mut self, file: &StepFile, binary: &BinaryInfo) {
+		let _ = write!(self.doc, "{}\n\n", binary_summary(&file.diff, binary));
+		if let Some(asset) = &file.asset {
+			let _ = write!(self.doc, "image::{asset}[\"{}\"]\n\n", file.path().replace('"', "\\\""));
+		}
+	}
+
+	/// Hunk header is marked with callout describing the hunk below the listing
+	fn hunk(&mut self, _file: &StepFile, hunk: &PatchInfo<'_>, index: usize) {
+		let (listing, callouts) = self.hunks.get_or_insert_default();
+		let no = index + 1;
+		let _ = writeln!(listing, "{} <{no}>", escape_callouts(&hunk.offset.to_string()));
+		if self.config.word_diff {
+			let mut md = MarkdownBuilder::default();
+			MarkdownRenderer::write_word_hunk(&mut md, hunk);
+			for line in md.build().lines() {
+				let _ = writeln!(listing, "{}", escape_callouts(line));
+			}
+		} else {
+			for line in 0..hunk.patch_lines() {
+				if let Some(line) = hunk.get_line(line) {
+					let _ = writeln!(listing, "{}", escape_callouts(line));
+				}
+			}
+		}
+		let count = |typ: DiffType| (0..hunk.patch_lines()).filter(|it| hunk.line_type(*it) == Some(typ)).count();
+		let _ = write!(callouts, "<{no}> Line {}: {} added, {} removed", hunk.offset.target_start, count(DiffType::Add), count(DiffType::Remove));
+		if let Some(heading) = hunk.offset.section_heading.as_deref().filter(|it| !it.is_empty()) {
+			let _ = write!(callouts, " in {}", code(heading.trim()));
+		}
+		callouts.push('\n');
+	}
+
+	/// Complete file as diff where lines added by this step are marked with `+`
+	fn full_file(&mut self, file: &StepFile, source: &str) {
+		let added = file.diff.diffs.added_lines();
+		let mut listing = String::with_capacity(source.len() + source.len() / 16);
+		for (no, line) in source.lines().enumerate() {
+			let marker = if added.binary_search(&(no as u64 + 1)).is_ok() { '+' } else { ' ' };
+			let _ = writeln!(listing, "{marker}{}", escape_callouts(line));
+		}
+		write_listing(&mut self.doc, "[source,diff]", &listing);
+		self.doc.push('\n');
+	}
+
+	/// Collapsible block with complete file after the diff
+	fn full_source(&mut self, file: &StepFile, source: &str) {
+		self.close_hunks();
+		let path = file.path();
+		let style = match language(path) {
+			"" => "[source]".to_string(),
+			language => format!("[source,{language}]"),
+		};
+		let _ = write!(self.doc, ".Full source of {}\n[%collapsible]\n====\n", code(path));
+		let mut listing = String::with_capacity(source.len() + 1);
+		for line in source.lines() {
+			let _ = writeln!(listing, "{}", escape_callouts(line));
+		}
+		write_listing(&mut self.doc, &style, &listing);
+		self.doc.push_str("====\n\n");
+	}
+
+	fn end_file(&mut self, _step: &Step, _file: &StepFile) {
+		self.close_hunks();
+	}
+
+	fn end_document(&mut self) -> String {
+		std::mem::take(&mut self.doc)
+	}
+}
+
+/// Append listing block of `content` ending with newline, delimiter is longer than any line of dashes inside
+fn write_listing(doc: &mut String, style: &str, content: &str) {
+	let longest = content.lines()
+		.filter(|it| it.len() >= 4 && it.bytes().all(|it| it == b'-'))
+		.map(str::len)
+		.max()
+		.unwrap_or(3);
+	let delimiter = "-".repeat(longest + 1);
+	let _ = write!(doc, "{style}\n{delimiter}\n{content}{delimiter}\n");
+}
+
+/// Escape callout markers such as `<1>`, `<.>` or `<!--1-->` at end of listing `line`,
+/// otherwise they would be numbered like callouts of hunks
+fn escape_callouts(line: &str) -> Cow<'_, str> {
+	let is_callout = |it: &str| {
+		let no = it.strip_prefix("<!--").and_then(|it| it.strip_suffix("-->"))
+			.or_else(|| it.strip_prefix('<').and_then(|it| it.strip_suffix('>')));
+		no.is_some_and(|it| it == "." || (!it.is_empty() && it.bytes().all(|it| it.is_ascii_digit())))
+	};
+	let mut markers = Vec::new();
+	let mut end = line.trim_end().len();
+	while let Some(start) = line[..end].rfind('<').filter(|it| is_callout(&line[*it..end])) {
+		markers.push(start);
+		// one space is allowed between markers
+		end = line[..start].strip_suffix(' ').unwrap_or(&line[..start]).len();
+	}
+	if markers.is_empty() {
+		return Cow::Borrowed(line);
+	}
+	let mut escaped = line.to_string();
+	for at in markers {
+		escaped.insert(at, '\\');
+	}
+	Cow::Owned(escaped)
+}
+
+/// Text shown literally, text with characters used by inline formatting is wrapped in `pass:c[]`
+fn escape(text: &str) -> String {
+	if text.contains(['*', '_', '`', '#', '^', '~', '+', '<', '[', ']', '{', '}', '\\']) {
+		format!("pass:c[{}]", text.replace(']', "\\]"))
+	} else {
+		text.to_string()
+	}
+}
+
+/// Literal monospace text
+fn code(text: &str) -> String {
+	if text.contains(['+', '`']) {
+		format!("`{}`", escape(text))
+	} else {
+		format!("`+{text}+`")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::Config;
+	use crate::generator::asciidoc::{escape, escape_callouts};
+	use crate::generator::{render_document, AsciidocRenderer, Step, StepFile};
+	use crate::git::{ChangeKind, DiffInfo, DiffOptions, GitLog, Patch};
+
+	fn step(no: usize, old: &str, new: &str) -> Step {
+		let mut diff = DiffInfo::new("diff --git a/m.rs b/m.rs".to_string());
+		diff.kind = ChangeKind::Modified;
+		diff.new_path = Some("m.rs".to_string());
+		diff.diffs = Patch::diff(old, new, &DiffOptions::default());
+		Step {
+			no,
+			log: GitLog::default(),
+			title: format!("Change *{no}*"),
+			body: String::new(),
+			files: vec![StepFile { diff, asset: None, source: None }],
+			part: None,
+		}
+	}
+
+	#[test]
+	fn test_render_asciidoc() {
+		let steps = [step(1, "a\n", "b\n"), step(2, "b\n", "c <1>\n")];
+		let config = Config { title: Some("Demo".to_string()), ..Config::default() };
+		let doc = render_document(&mut AsciidocRenderer::new(&config), &config, &steps, &steps);
+		let (_, second) = doc.split_once("[[step-2]]").unwrap();
+		assert_eq!(second, "\n== Step 2: pass:c[Change *2*]\n\n=== `+m.rs+`\n\n\
+			Last changed in <<step-1>>.\n\n[source,diff]\n----\n@@ -1 +1 @@ <1>\n-b\n+c \\<1>\n----\n\
+			<1> Line 1: 1 added, 1 removed\n\n");
+
+		// step which is not rendered has no anchor to refer to
+		let doc = render_document(&mut AsciidocRenderer::new(&config), &config, &steps, &steps[1..]);
+		assert!(!doc.contains("Last changed"), "{doc}");
+
+		let mut steps = [step(1, "a\n", "a\n")];
+		let diff = &mut steps[0].files[0].diff;
+		diff.kind = ChangeKind::ModeChange;
+		(diff.old_mode, diff.new_mode) = (Some(0o100644), Some(0o100755));
+		let doc = render_document(&mut AsciidocRenderer::new(&config), &config, &steps, &steps);
+		assert!(doc.contains("=== `+m.rs+` (mode changed from `+100644+` to `+100755+`)\n"), "{doc}");
+	}
+
+	#[test]
+	fn test_escape() {
+		assert_eq!(escape("plain: text"), "plain: text");
+		assert_eq!(escape("[wip] a_b"), "pass:c[[wip\\] a_b]");
+		assert_eq!(escape_callouts("a <1> <2>"), "a \\<1> \\<2>");
+		assert_eq!(escape_callouts("x<.><!--3-->  "), "x\\<.>\\<!--3-->  ");
+		assert_eq!(escape_callouts("Vec<T> <12a>"), "Vec<T> <12a>");
+	}
+}
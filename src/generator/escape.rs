@@ -0,0 +1,89 @@
+use std::borrow::Cow;
+
+/// Characters with inline meaning in CommonMark or GitHub flavored markdown
+const SPECIAL: &[char] = &['\\', '`', '*', '_', '[', ']', '<', '>', '|', '~', '#', '&'];
+
+/// Escape `text` so it is rendered literally, including block markers at the start of a line
+pub fn text(text: &str) -> Cow<'_, str> {
+	let mut escaped = String::new();
+	// whether only spaces and digits are seen since start of line
+	let (mut line_start, mut digits) = (true, false);
+	for (at, ch) in text.char_indices() {
+		let escape = SPECIAL.contains(&ch)
+			|| (line_start && !digits && matches!(ch, '-' | '+' | '='))
+			|| (line_start && digits && matches!(ch, '.' | ')'));
+		if escape && escaped.is_empty() {
+			escaped.reserve(text.len() + 8);
+			escaped.push_str(&text[..at]);
+		}
+		if escape {
+			escaped.push('\\');
+		}
+		if !escaped.is_empty() {
+			escaped.push(ch);
+		}
+		match ch {
+			'\n' => (line_start, digits) = (true, false),
+			' ' if !digits => {}
+			'0'..='9' => digits = line_start,
+			_ => line_start = false,
+		}
+	}
+	if escaped.is_empty() {
+		Cow::Borrowed(text)
+	} else {
+		Cow::Owned(escaped)
+	}
+}
+
+/// Escape text inside `[...]` of link or image, line breaks become spaces
+pub fn link_text(text: &str) -> Cow<'_, str> {
+	if text.contains(['\n', '\r']) {
+		Cow::Owned(self::text(&text.replace(['\n', '\r'], " ")).into_owned())
+	} else {
+		self::text(text)
+	}
+}
+
+/// Percent-encode link destination, characters allowed in URL are kept and existing escapes are not encoded twice
+pub fn url(url: &str) -> Cow<'_, str> {
+	let bytes = url.as_bytes();
+	let keep = |at: usize| match bytes[at] {
+		b'%' => bytes.get(at + 1..at + 3).is_some_and(|it| it.iter().all(u8::is_ascii_hexdigit)),
+		// parentheses and angle brackets would end link destination
+		b'(' | b')' | b'<' | b'>' => false,
+		byte => byte.is_ascii_alphanumeric() || b"-._~!$&'*+,;=:@/?#[]".contains(&byte),
+	};
+	if (0..bytes.len()).all(keep) {
+		return Cow::Borrowed(url);
+	}
+	let mut encoded = String::with_capacity(url.len() + 16);
+	for (at, byte) in bytes.iter().enumerate() {
+		if keep(at) {
+			encoded.push(*byte as char);
+		} else {
+			encoded.push_str(&format!("%{byte:02X}"));
+		}
+	}
+	Cow::Owned(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::generator::escape::{link_text, text, url};
+
+	#[test]
+	fn test_text() {
+		assert_eq!(text("plain words, 1.5 and a-b"), "plain words, 1.5 and a-b");
+		assert_eq!(text("fix *bold* _it_ [x] <b> a|b #1"), "fix \\*bold\\* \\_it\\_ \\[x\\] \\<b\\> a\\|b \\#1");
+		assert_eq!(text("- item\n  + more\n10. ten\n3) three"), "\\- item\n  \\+ more\n10\\. ten\n3\\) three");
+		assert_eq!(link_text("a]\nb"), "a\\] b");
+	}
+
+	#[test]
+	fn test_url() {
+		assert_eq!(url("https://example.com/a?b=c#d"), "https://example.com/a?b=c#d");
+		assert_eq!(url("assets/my logo (1).png"), "assets/my%20logo%20%281%29.png");
+		assert_eq!(url("100%25 ü%"), "100%25%20%C3%BC%25");
+	}
+}
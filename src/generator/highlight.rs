@@ -0,0 +1,252 @@
+use std::ops::Range;
+
+/// Class of highlighted token, used as CSS class name
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TokenKind {
+	Plain,
+	Keyword,
+	String,
+	Comment,
+	Number,
+}
+
+impl TokenKind {
+	pub fn class(&self) -> Option<&'static str> {
+		match self {
+			TokenKind::Plain => None,
+			TokenKind::Keyword => Some("kw"),
+			TokenKind::String => Some("str"),
+			TokenKind::Comment => Some("com"),
+			TokenKind::Number => Some("num"),
+		}
+	}
+}
+
+/// Lexical rules of a language, good enough to colour code but not to parse it
+struct Syntax {
+	keywords: &'static [&'static str],
+	line_comment: Option<&'static str>,
+	block_comment: Option<(&'static str, &'static str)>,
+	quotes: &'static [char],
+}
+
+const PLAIN: Syntax = Syntax {
+	keywords: &[],
+	line_comment: None,
+	block_comment: None,
+	quotes: &[],
+};
+
+const RUST: Syntax = Syntax {
+	keywords: &[
+		"as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+		"fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+		"self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+	],
+	line_comment: Some("//"),
+	block_comment: Some(("/*", "*/")),
+	quotes: &['"'],
+};
+
+const C_LIKE: Syntax = Syntax {
+	keywords: &[
+		"abstract", "auto", "break", "case", "catch", "char", "class", "const", "continue", "default", "delete",
+		"do", "double", "else", "enum", "export", "extends", "false", "final", "float", "for", "func", "function",
+		"fun", "go", "if", "implements", "import", "int", "interface", "let", "long", "namespace", "new", "null",
+		"package", "private", "protected", "public", "return", "short", "static", "struct", "switch", "this",
+		"throw", "true", "try", "typedef", "unsigned", "val", "var", "void", "while",
+	],
+	line_comment: Some("//"),
+	block_comment: Some(("/*", "*/")),
+	quotes: &['"', '\'', '`'],
+};
+
+const PYTHON: Syntax = Syntax {
+	keywords: &[
+		"and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else",
+		"except", "False", "finally", "for", "from", "if", "import", "in", "is", "lambda", "None", "not", "or",
+		"pass", "raise", "return", "True", "try", "while", "with", "yield",
+	],
+	line_comment: Some("#"),
+	block_comment: None,
+	quotes: &['"', '\''],
+};
+
+const SHELL: Syntax = Syntax {
+	keywords: &["case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local", "then", "while"],
+	line_comment: Some("#"),
+	block_comment: None,
+	quotes: &['"', '\''],
+};
+
+const CONFIG: Syntax = Syntax {
+	keywords: &["true", "false", "null"],
+	line_comment: Some("#"),
+	block_comment: None,
+	quotes: &['"', '\''],
+};
+
+const MARKUP: Syntax = Syntax {
+	keywords: &[],
+	line_comment: None,
+	block_comment: Some(("<!--", "-->")),
+	quotes: &['"'],
+};
+
+/// Split lines into coloured tokens, block comments are tracked across lines
+pub struct Highlighter {
+	syntax: &'static Syntax,
+	in_comment: bool,
+}
+
+impl Highlighter {
+	/// Highlighter for fence language returned by [language]
+	pub fn new(language: &str) -> Self {
+		let syntax = match language {
+			"rust" => &RUST,
+			"javascript" | "typescript" | "go" | "c" | "cpp" | "java" | "kotlin" | "css" => &C_LIKE,
+			"python" => &PYTHON,
+			"sh" | "dockerfile" | "makefile" => &SHELL,
+			"toml" | "yaml" | "json" => &CONFIG,
+			"html" | "markdown" => &MARKUP,
+			_ => &PLAIN,
+		};
+		Self { syntax, in_comment: false }
+	}
+
+	/// Byte ranges of tokens covering the whole `line`
+	pub fn line(&mut self, line: &str) -> Vec<(TokenKind, Range<usize>)> {
+		let mut tokens = Vec::new();
+		let mut at = 0;
+		let mut plain = 0;
+		let bytes = line.as_bytes();
+		while at < line.len() {
+			let rest = &line[at..];
+			let (kind, len) = if self.in_comment {
+				let (_, close) = self.syntax.block_comment.unwrap_or_default();
+				match rest.find(close) {
+					Some(end) => {
+						self.in_comment = false;
+						(TokenKind::Comment, end + close.len())
+					}
+					None => (TokenKind::Comment, rest.len()),
+				}
+			} else if self.syntax.line_comment.is_some_and(|it| rest.starts_with(it)) {
+				(TokenKind::Comment, rest.len())
+			} else if let Some((open, _)) = self.syntax.block_comment.filter(|(open, _)| rest.starts_with(open)) {
+				self.in_comment = true;
+				(TokenKind::Comment, open.len())
+			} else if let Some(quote) = self.syntax.quotes.iter().find(|it| rest.starts_with(**it)) {
+				(TokenKind::String, string_len(rest, *quote))
+			} else if is_word(rest) && (at == 0 || !is_word_byte(bytes[at - 1])) {
+				let len = rest.find(|it: char| !(it.is_alphanumeric() || it == '_')).unwrap_or(rest.len());
+				let word = &rest[..len];
+				if word.starts_with(|it: char| it.is_ascii_digit()) {
+					(TokenKind::Number, len)
+				} else if self.syntax.keywords.contains(&word) {
+					(TokenKind::Keyword, len)
+				} else {
+					(TokenKind::Plain, len)
+				}
+			} else {
+				(TokenKind::Plain, rest.chars().next().map(char::len_utf8).unwrap_or(1))
+			};
+			if kind == TokenKind::Plain {
+				at += len;
+				continue;
+			}
+			if plain < at {
+				tokens.push((TokenKind::Plain, plain..at));
+			}
+			match tokens.last_mut() {
+				// comment opened and continued in the same line
+				Some((last, range)) if *last == kind && range.end == at => range.end = at + len,
+				_ => tokens.push((kind, at..at + len)),
+			}
+			at += len;
+			plain = at;
+		}
+		if plain < line.len() {
+			tokens.push((TokenKind::Plain, plain..line.len()));
+		}
+		tokens
+	}
+}
+
+fn is_word(text: &str) -> bool {
+	text.starts_with(|it: char| it.is_alphanumeric() || it == '_')
+}
+
+fn is_word_byte(byte: u8) -> bool {
+	byte.is_ascii_alphanumeric() || byte == b'_' || byte >= 0x80
+}
+
+/// Length of string literal at start of `text` including quotes, until end of line if not closed
+fn string_len(text: &str, quote: char) -> usize {
+	let mut escaped = false;
+	for (at, ch) in text.char_indices().skip(1) {
+		match ch {
+			_ if escaped => escaped = false,
+			'\\' => escaped = true,
+			_ if ch == quote => return at + ch.len_utf8(),
+			_ => {}
+		}
+	}
+	text.len()
+}
+
+/// Code fence language guessed from file extension
+pub fn language(path: &str) -> &'static str {
+	let name = path.rsplit('/').next().unwrap_or(path);
+	let ext = name.rsplit_once('.').map(|it| it.1.to_ascii_lowercase()).unwrap_or_default();
+	match ext.as_str() {
+		"rs" => "rust",
+		"toml" => "toml",
+		"md" => "markdown",
+		"js" | "mjs" | "cjs" => "javascript",
+		"ts" => "typescript",
+		"py" => "python",
+		"go" => "go",
+		"c" | "h" => "c",
+		"cpp" | "cc" | "hpp" => "cpp",
+		"java" => "java",
+		"kt" => "kotlin",
+		"sh" | "bash" => "sh",
+		"json" => "json",
+		"yml" | "yaml" => "yaml",
+		"html" | "htm" => "html",
+		"css" => "css",
+		"sql" => "sql",
+		_ if name == "Dockerfile" => "dockerfile",
+		_ if name == "Makefile" => "makefile",
+		_ => "",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::generator::highlight::{Highlighter, TokenKind};
+
+	#[test]
+	fn test_highlight() {
+		let mut highlighter = Highlighter::new("rust");
+		let line = "let s = \"a\\\"b\"; // 42";
+		let tokens = highlighter.line(line)
+			.into_iter()
+			.map(|(kind, range)| (kind, &line[range]))
+			.collect::<Vec<_>>();
+		assert_eq!(tokens, vec![
+			(TokenKind::Keyword, "let"),
+			(TokenKind::Plain, " s = "),
+			(TokenKind::String, "\"a\\\"b\""),
+			(TokenKind::Plain, "; "),
+			(TokenKind::Comment, "// 42"),
+		]);
+
+		// block comment continue on next line
+		assert_eq!(highlighter.line("/* start").len(), 1);
+		let tokens = highlighter.line("end */ x1 1");
+		assert_eq!(tokens[0], (TokenKind::Comment, 0..6));
+		assert_eq!(tokens.last(), Some(&(TokenKind::Number, 10..11)));
+	}
+}
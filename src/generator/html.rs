@@ -0,0 +1,378 @@
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::config::Config;
+use crate::generator::highlight::{language, Highlighter, TokenKind};
+use crate::generator::tutorial::binary_summary;
+use crate::generator::{DocumentRenderer, Part, Step, StepFile};
+use crate::git::{BinaryInfo, ChangeKind, DiffType, PatchInfo};
+
+/// Style embedded into every page, so output does not depend on any external file
+const STYLE: &str = r#"
+body{margin:0;font:16px/1.5 -apple-system,"Segoe UI",Helvetica,Arial,sans-serif;color:#1f2328;background:#fff}
+nav{position:fixed;top:0;left:0;bottom:0;width:18rem;overflow-y:auto;padding:1rem;box-sizing:border-box;background:#f6f8fa;border-right:1px solid #d0d7de;font-size:14px}
+nav ol{padding-left:1.2rem}nav ul{padding-left:1rem;list-style:none}nav a{color:#0969da;text-decoration:none}nav a:hover{text-decoration:underline}
+nav .files a{color:#57606a;font-family:ui-monospace,monospace;font-size:12px}
+main{margin-left:18rem;padding:1rem 2rem;max-width:64rem}
+h2,h3{position:relative}.anchor{position:absolute;left:-1.2em;color:#8c959f;text-decoration:none;visibility:hidden}
+h2:hover .anchor,h3:hover .anchor{visibility:visible}
+.note{color:#57606a;font-weight:normal;font-size:14px}
+table.diff{width:100%;border-collapse:collapse;font:13px/1.45 ui-monospace,SFMono-Regular,Menlo,monospace;border:1px solid #d0d7de;margin-bottom:1rem}
+table.diff td{padding:0 .5rem;white-space:pre-wrap;vertical-align:top}
+td.ln{width:1%;min-width:2.5rem;text-align:right;color:#8c959f;user-select:none}td.sign{width:1%;user-select:none}
+tr.hunk td{background:#ddf4ff;color:#57606a}tr.add{background:#e6ffec}tr.del{background:#ffebe9}
+tr.add mark{background:#abf2bc}tr.del mark{background:#ffc1c0}mark{color:inherit}
+pre{background:#f6f8fa;padding:.5rem 1rem;overflow-x:auto;font-size:13px}
+.kw{color:#cf222e}.str{color:#0a3069}.com{color:#6e7781;font-style:italic}.num{color:#0550ae}
+.pager{display:flex;justify-content:space-between;margin:2rem 0}
+"#;
+
+/// Render self-contained html page with side navigation, either every step or a single step per page
+pub struct HtmlRenderer<'a> {
+	config: &'a Config,
+	/// Current page number (0 for index) when every step has its own page
+	page: Option<usize>,
+	/// Navigation of every step
+	nav: String,
+	/// Number and title of every step, for links to previous and next page
+	steps: Vec<(usize, String)>,
+	body: String,
+	/// Table of hunks is open
+	in_hunks: bool,
+}
+
+impl<'a> HtmlRenderer<'a> {
+	/// Render every step into one page
+	pub fn new(config: &'a Config) -> Self {
+		Self {
+			config,
+			page: None,
+			nav: String::new(),
+			steps: Vec::new(),
+			body: String::new(),
+			in_hunks: false,
+		}
+	}
+
+	/// Render page of step `no` only, index page listing every step when `no` is 0
+	pub fn page(config: &'a Config, no: usize) -> Self {
+		Self { page: Some(no), ..Self::new(config) }
+	}
+
+	fn close_hunks(&mut self) {
+		if self.in_hunks {
+			self.body.push_str("</table>\n");
+			self.in_hunks = false;
+		}
+	}
+
+	/// Links to pages of steps before and after the current one
+	fn write_pager(&mut self, no: usize) {
+		let at = self.steps.iter().position(|it| it.0 == no).unwrap_or_default();
+		let html = &mut self.body;
+		html.push_str("<div class=\"pager\">");
+		match at.checked_sub(1).and_then(|it| self.steps.get(it)) {
+			Some((prev, title)) => {
+				let _ = write!(html, "<a href=\"{}\">← {}</a>", page_name(*prev), escape(title));
+			}
+			None => html.push_str("<span></span>"),
+		}
+		if let Some((next, title)) = self.steps.get(at + 1) {
+			let _ = write!(html, "<a href=\"{}\">{} →</a>", page_name(*next), escape(title));
+		}
+		html.push_str("</div>\n");
+	}
+}
+
+/// File name of page of step `no` when every step has its own page
+pub(crate) fn page_name(no: usize) -> String {
+	format!("step-{no}.html")
+}
+
+impl DocumentRenderer for HtmlRenderer<'_> {
+	fn begin_document(&mut self, title: Option<&str>, steps: &[Step]) {
+		self.nav = navigation(steps, self.page);
+		self.steps = steps.iter().map(|it| (it.no, it.title.clone())).collect();
+		if let Some(title) = title {
+			let _ = writeln!(self.body, "<h1>{}</h1>", escape(title));
+		}
+		if self.page == Some(0) {
+			self.body.push_str("<ol>\n");
+			for step in steps {
+				let _ = writeln!(self.body, "<li><a href=\"{}\">{}</a></li>", page_name(step.no), escape(&step.title));
+			}
+			self.body.push_str("</ol>\n");
+		}
+	}
+
+	fn begin_part(&mut self, part: &Part) {
+		let Some(title) = &part.title else {
+			return;
+		};
+		let _ = writeln!(self.body, "<h1 class=\"part\">{}</h1>", escape(title));
+		if let Some(intro) = &part.intro {
+			write_prose(&mut self.body, intro);
+		}
+	}
+
+	fn begin_step(&mut self, step: &Step) {
+		let id = step_id(step.no);
+		let _ = writeln!(self.body, "<section class=\"step\" id=\"{id}\">");
+		let _ = writeln!(self.body, "<h2><a class=\"anchor\" href=\"#{id}\">#</a>Step {}: {}</h2>", step.no, escape(&step.title));
+	}
+
+	fn prose(&mut self, _step: &Step, text: &str) {
+		write_prose(&mut self.body, text);
+	}
+
+	fn begin_file(&mut self, step: &Step, file: &StepFile) {
+		let diff = &file.diff;
+		let id = file_id(step.no, diff.path());
+		let _ = write!(self.body, "<section class=\"file\" id=\"{id}\">\n<h3><a class=\"anchor\" href=\"#{id}\">#</a><code>{}</code>", escape(diff.path()));
+		let note = match diff.kind {
+			ChangeKind::Added => Some("new file".to_string()),
+			ChangeKind::Deleted => Some("deleted".to_string()),
+			ChangeKind::Renamed | ChangeKind::Copied => Some(format!(
+				"{} from <code>{}</code>",
+				diff.kind,
+				escape(diff.old_path.as_deref().unwrap_or_default())
+			)),
+			ChangeKind::ModeChange => Some(format!(
+				"mode changed from <code>{:o}</code> to <code>{:o}</code>",
+				diff.old_mode.unwrap_or_default(),
+				diff.new_mode.unwrap_or_default()
+			)),
+			ChangeKind::Modified => None,
+		};
+		if let Some(note) = note {
+			let _ = write!(self.body, " <span class=\"note\">({note})</span>");
+		}
+		self.body.push_str("</h3>\n");
+	}
+
+	fn binary(&mut self, file: &StepFile, binary: &BinaryInfo) {
+		let _ = writeln!(self.body, "<p>{}</p>", escape(&binary_summary(&file.diff, binary)));
+		if let Some(asset) = &file.asset {
+			let _ = writeln!(self.body, "<p><img src=\"{}\" alt=\"{}\"></p>", escape(asset), escape(file.path()));
+		}
+	}
+
+	/// Hunk as rows of old and new line numbers and coloured lines, changed words are marked
+	fn hunk(&mut self, file: &StepFile, hunk: &PatchInfo<'_>, _index: usize) {
+		if !self.in_hunks {
+			self.body.push_str("<table class=\"diff\">\n");
+			self.in_hunks = true;
+		}
+		let html = &mut self.body;
+		let language = language(file.path());
+		let _ = writeln!(html, "<tr class=\"hunk\"><td colspan=\"4\">{}</td></tr>", escape(&hunk.offset.to_string()));
+		// old and new side have their own comment state
+		let (mut old_highlighter, mut new_highlighter) = (Highlighter::new(language), Highlighter::new(language));
+		let (mut old, mut new) = (hunk.offset.source_start, hunk.offset.target_start);
+		for line in 0..hunk.patch_lines() {
+			let (Some(typ), Some(text)) = (hunk.line_type(line), hunk.get_line(line)) else { continue };
+			let content = &text[1..];
+			let spans = hunk.word_spans(line)
+				.unwrap_or_default()
+				.into_iter()
+				.map(|it| it.start - 1..it.end - 1)
+				.collect::<Vec<_>>();
+			let (class, sign, old_no, new_no, tokens) = match typ {
+				DiffType::None => {
+					old_highlighter.line(content);
+					let row = ("ctx", " ", Some(old), Some(new), new_highlighter.line(content));
+					old += 1;
+					new += 1;
+					row
+				}
+				DiffType::Remove => {
+					old += 1;
+					("del", "-", Some(old - 1), None, old_highlighter.line(content))
+				}
+				DiffType::Add => {
+					new += 1;
+					("add", "+", None, Some(new - 1), new_highlighter.line(content))
+				}
+				DiffType::NoNewline => {
+					let _ = writeln!(html, "<tr class=\"ctx\"><td class=\"ln\"></td><td class=\"ln\"></td><td class=\"sign\"></td><td class=\"com\">{}</td></tr>", escape(content));
+					continue;
+				}
+			};
+			let number = |no: Option<u64>| no.map(|it| it.to_string()).unwrap_or_default();
+			let _ = write!(
+				html,
+				"<tr class=\"{class}\"><td class=\"ln\">{}</td><td class=\"ln\">{}</td><td class=\"sign\">{sign}</td><td>",
+				number(old_no),
+				number(new_no)
+			);
+			write_code(html, content, &tokens, &spans);
+			html.push_str("</td></tr>\n");
+		}
+	}
+
+	/// Complete file where lines added by this step are coloured
+	fn full_file(&mut self, file: &StepFile, source: &str) {
+		let added = file.diff.diffs.added_lines();
+		let mut highlighter = Highlighter::new(language(file.path()));
+		let html = &mut self.body;
+		html.push_str("<table class=\"diff\">\n");
+		for (no, line) in source.lines().enumerate() {
+			let no = no as u64 + 1;
+			let (class, sign) = if added.binary_search(&no).is_ok() { ("add", "+") } else { ("ctx", " ") };
+			let _ = write!(html, "<tr class=\"{class}\"><td class=\"ln\">{no}</td><td class=\"sign\">{sign}</td><td>");
+			write_code(html, line, &highlighter.line(line), &[]);
+			html.push_str("</td></tr>\n");
+		}
+		html.push_str("</table>\n");
+	}
+
+	/// Collapsible highlighted complete file
+	fn full_source(&mut self, file: &StepFile, source: &str) {
+		self.close_hunks();
+		let mut highlighter = Highlighter::new(language(file.path()));
+		let html = &mut self.body;
+		let _ = write!(html, "<details>\n<summary>Full source of <code>{}</code></summary>\n<pre><code>", escape(file.path()));
+		for line in source.lines() {
+			write_code(html, line, &highlighter.line(line), &[]);
+			html.push('\n');
+		}
+		html.push_str("</code></pre>\n</details>\n");
+	}
+
+	fn end_file(&mut self, _step: &Step, _file: &StepFile) {
+		self.close_hunks();
+		self.body.push_str("</section>\n");
+	}
+
+	fn end_step(&mut self, step: &Step) {
+		if self.page.is_some() {
+			self.write_pager(step.no);
+		}
+		self.body.push_str("</section>\n");
+	}
+
+	fn end_document(&mut self) -> String {
+		let title = escape(self.config.title.as_deref().unwrap_or("Tutorial"));
+		format!(
+			"<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+			<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+			<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n{}<main>\n{}</main>\n</body>\n</html>\n",
+			self.nav,
+			std::mem::take(&mut self.body)
+		)
+	}
+}
+
+/// Side navigation with link to every step and its files,
+/// `page` is the current page number (0 for index) when steps are split into pages
+fn navigation(steps: &[Step], page: Option<usize>) -> String {
+	let mut nav = String::from("<nav>\n<ol>\n");
+	for step in steps {
+		let base = match page {
+			Some(page) if page != step.no => page_name(step.no),
+			_ => String::new(),
+		};
+		let _ = writeln!(nav, "<li><a href=\"{base}#{}\">{}</a>", step_id(step.no), escape(&step.title));
+		// only list files of the step currently shown
+		if page.is_none_or(|it| it == step.no) && !step.files.is_empty() {
+			nav.push_str("<ul class=\"files\">\n");
+			for file in &step.files {
+				let _ = writeln!(nav, "<li><a href=\"#{}\">{}</a></li>", file_id(step.no, file.path()), escape(file.path()));
+			}
+			nav.push_str("</ul>\n");
+		}
+		nav.push_str("</li>\n");
+	}
+	nav.push_str("</ol>\n</nav>\n");
+	nav
+}
+
+/// Paragraphs of `text` separated by blank lines, line breaks are kept
+fn write_prose(html: &mut String, text: &str) {
+	for paragraph in text.split("\n\n").map(str::trim).filter(|it| !it.is_empty()) {
+		let _ = writeln!(html, "<p>{}</p>", escape(paragraph).replace('\n', "<br>\n"));
+	}
+}
+
+fn step_id(no: usize) -> String {
+	format!("step-{no}")
+}
+
+fn file_id(no: usize, path: &str) -> String {
+	let mut id = format!("step-{no}-");
+	for ch in path.chars() {
+		if ch.is_alphanumeric() {
+			id.extend(ch.to_lowercase());
+		} else if !id.ends_with('-') {
+			id.push('-');
+		}
+	}
+	id.trim_end_matches('-').to_string()
+}
+
+/// Escaped `line` with highlighted tokens, text inside `spans` is wrapped in `<mark>`
+fn write_code(html: &mut String, line: &str, tokens: &[(TokenKind, Range<usize>)], spans: &[Range<usize>]) {
+	for (kind, range) in tokens {
+		if let Some(class) = kind.class() {
+			let _ = write!(html, "<span class=\"{class}\">");
+		}
+		// split token where changed words start or end
+		let mut cuts = vec![range.start, range.end];
+		cuts.extend(spans.iter()
+			.flat_map(|it| [it.start, it.end])
+			.filter(|it| range.contains(it) && *it != range.start));
+		cuts.sort_unstable();
+		cuts.dedup();
+		for part in cuts.windows(2) {
+			let changed = spans.iter().any(|it| it.start <= part[0] && part[1] <= it.end);
+			if changed {
+				html.push_str("<mark>");
+			}
+			html.push_str(&escape(&line[part[0]..part[1]]));
+			if changed {
+				html.push_str("</mark>");
+			}
+		}
+		if kind.class().is_some() {
+			html.push_str("</span>");
+		}
+	}
+}
+
+/// Escape text for use in element content and quoted attribute values
+pub(crate) fn escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for ch in text.chars() {
+		match ch {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&#39;"),
+			_ => escaped.push(ch),
+		}
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::generator::highlight::Highlighter;
+	use crate::generator::html::{escape, file_id, write_code};
+
+	#[test]
+	fn test_escape() {
+		assert_eq!(escape("<a href=\"x\">&'</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;");
+		assert_eq!(file_id(3, "src/Main.rs"), "step-3-src-main-rs");
+		assert_eq!(file_id(1, "a  b/"), "step-1-a-b");
+	}
+
+	#[test]
+	fn test_write_code() {
+		let line = "let total = a < count;";
+		let mut html = String::new();
+		let spans = vec![16..21, 21..21];
+		write_code(&mut html, line, &Highlighter::new("rust").line(line), &spans);
+		assert_eq!(html, "<span class=\"kw\">let</span> total = a &lt; <mark>count</mark>;");
+	}
+}
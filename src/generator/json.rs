@@ -0,0 +1,59 @@
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::generator::Step;
+
+/// Version of exported document, increased on incompatible change of its layout
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Root of exported document
+#[derive(Serialize)]
+struct Document<'a> {
+	version: u32,
+	title: Option<&'a str>,
+	steps: &'a [Step],
+}
+
+/// Pretty printed JSON document of `steps`
+pub(crate) fn to_json(config: &Config, steps: &[Step]) -> Result<String> {
+	let document = Document {
+		version: SCHEMA_VERSION,
+		title: config.title.as_deref(),
+		steps,
+	};
+	Ok(serde_json::to_string_pretty(&document)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::Config;
+	use crate::generator::json::to_json;
+	use crate::generator::{Step, StepFile};
+	use crate::git::{ChangeKind, DiffInfo, DiffOptions, GitLog, Patch};
+
+	#[test]
+	fn test_to_json() {
+		let mut diff = DiffInfo::new("diff --git a/m.rs b/m.rs".to_string());
+		diff.kind = ChangeKind::Modified;
+		diff.diffs = Patch::diff("a\nb\n", "a\nc", &DiffOptions::default());
+		let step = Step {
+			no: 1,
+			log: GitLog { hash: "abc".to_string(), ..GitLog::default() },
+			title: "Use c".to_string(),
+			body: String::new(),
+			files: vec![StepFile { diff, asset: None, source: None }],
+			part: None,
+		};
+		let json: serde_json::Value = serde_json::from_str(&to_json(&Config::default(), &[step]).unwrap()).unwrap();
+		assert_eq!(json["version"], 1);
+		assert_eq!(json["steps"][0]["commit"]["hash"], "abc");
+		let file = &json["steps"][0]["files"][0];
+		assert_eq!((&file["kind"], &file["new_path"]), (&"modified".into(), &"m.rs".into()));
+		let lines = &file["hunks"][0]["lines"];
+		assert_eq!(lines[0], serde_json::json!({ "type": "none", "old": 1, "new": 1, "content": "a", "missing_newline": false }));
+		assert_eq!(lines[1]["old"], 2);
+		assert_eq!((&lines[2]["new"], &lines[2]["missing_newline"]), (&2.into(), &true.into()));
+		assert_eq!(lines.as_array().map(Vec::len), Some(3));
+	}
+}
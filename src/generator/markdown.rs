@@ -0,0 +1,232 @@
+use crate::config::Config;
+use crate::generator::highlight::language;
+use crate::generator::html::escape;
+use crate::generator::tutorial::binary_summary;
+use crate::generator::{DocumentRenderer, MarkdownBuilder, Part, Step, StepFile};
+use crate::git::{BinaryInfo, ChangeKind, DiffType, PatchInfo};
+
+/// Render document as a single markdown file, hunks of a file share one `diff` code block
+pub struct MarkdownRenderer<'a> {
+	config: &'a Config,
+	/// Heading level of steps, files are one level below
+	level: usize,
+	md: MarkdownBuilder,
+	/// Hunks of current file, written as one code block when file ends
+	hunks: Option<MarkdownBuilder>,
+}
+
+impl<'a> MarkdownRenderer<'a> {
+	pub fn new(config: &'a Config) -> Self {
+		Self {
+			config,
+			level: 2,
+			md: MarkdownBuilder::default(),
+			hunks: None,
+		}
+	}
+
+	/// Render chapter of a book where step is the top heading and document title is left out
+	pub fn chapter(config: &'a Config) -> Self {
+		Self { level: 1, ..Self::new(config) }
+	}
+
+	fn close_hunks(&mut self) {
+		if let Some(hunks) = self.hunks.take() {
+			let info = if self.config.word_diff { "" } else { "diff" };
+			self.md.code_block(info, hunks.build());
+		}
+	}
+
+	/// Hunk in `git diff --word-diff=plain` style, modified lines are merged into one line
+	pub(crate) fn write_word_hunk(md: &mut MarkdownBuilder, hunk: &PatchInfo<'_>) {
+		for line in 0..hunk.patch_lines() {
+			let (Some(typ), Some(text)) = (hunk.line_type(line), hunk.get_line(line)) else { continue };
+			let content = &text[1..];
+			match (typ, hunk.word_diff(line)) {
+				(DiffType::None, _) => {
+					md.append(content);
+				}
+				(DiffType::Remove, Some(words)) => {
+					for (typ, text) in words.segments {
+						match typ {
+							DiffType::Remove => md.append("[-").append(text).append("-]"),
+							DiffType::Add => md.append("{+").append(text).append("+}"),
+							_ => md.append(text),
+						};
+					}
+				}
+				// already merged into removed line
+				(DiffType::Add, Some(_)) | (DiffType::NoNewline, _) => continue,
+				(typ, None) => {
+					let (open, close) = if typ == DiffType::Remove { ("[-", "-]") } else { ("{+", "+}") };
+					let body = content.trim_start();
+					md.append(&content[..content.len() - body.len()])
+						.append(open)
+						.append(body)
+						.append(close);
+				}
+			}
+			md.append("\n");
+		}
+	}
+}
+
+impl DocumentRenderer for MarkdownRenderer<'_> {
+	fn begin_document(&mut self, title: Option<&str>, _steps: &[Step]) {
+		if let Some(title) = title.filter(|_| self.level > 1) {
+			self.md.title(title);
+		}
+	}
+
+	/// Part title as heading above its steps followed by intro, chapters of a book leave it to `SUMMARY.md`
+	fn begin_part(&mut self, part: &Part) {
+		let Some(title) = part.title.as_deref().filter(|_| self.level > 1) else {
+			return;
+		};
+		self.md.heading(self.level - 1)
+			.text(title)
+			.append("\n\n");
+		if let Some(intro) = &part.intro {
+			self.md.append(intro)
+				.append("\n\n");
+		}
+	}
+
+	fn begin_step(&mut self, step: &Step) {
+		self.md.heading(self.level)
+			.append(format!("Step {}: ", step.no))
+			.text(&step.title)
+			.append("\n\n");
+	}
+
+	fn prose(&mut self, _step: &Step, text: &str) {
+		self.md.append(text)
+			.append("\n\n");
+	}
+
+	fn begin_file(&mut self, _step: &Step, file: &StepFile) {
+		let diff = &file.diff;
+		let md = &mut self.md;
+		md.heading(self.level + 1)
+			.code(diff.path());
+		match diff.kind {
+			ChangeKind::Added => {
+				md.append(" (new file)");
+			}
+			ChangeKind::Deleted => {
+				md.append(" (deleted)");
+			}
+			ChangeKind::Renamed | ChangeKind::Copied => {
+				md.append(format!(" ({} from ", diff.kind))
+					.code(diff.old_path.as_deref().unwrap_or_default())
+					.append(")");
+			}
+			ChangeKind::ModeChange => {
+				md.append(format!(
+					" (mode changed from `{:o}` to `{:o}`)",
+					diff.old_mode.unwrap_or_default(),
+					diff.new_mode.unwrap_or_default()
+				));
+			}
+			ChangeKind::Modified => {}
+		}
+		md.append("\n\n");
+	}
+
+	fn binary(&mut self, file: &StepFile, binary: &BinaryInfo) {
+		self.md.append(binary_summary(&file.diff, binary))
+			.append("\n\n");
+		if let Some(asset) = &file.asset {
+			self.md.escaped_image(file.path(), asset)
+				.append("\n\n");
+		}
+	}
+
+	fn hunk(&mut self, _file: &StepFile, hunk: &PatchInfo<'_>, _index: usize) {
+		let md = self.hunks.get_or_insert_with(MarkdownBuilder::default);
+		md.append(hunk.offset.to_string())
+			.append("\n");
+		if self.config.word_diff {
+			Self::write_word_hunk(md, hunk);
+			return;
+		}
+		for line in 0..hunk.patch_lines() {
+			if let Some(line) = hunk.get_line(line) {
+				md.append(line)
+					.append("\n");
+			}
+		}
+	}
+
+	/// Complete file as diff where lines added by this step are marked with `+`
+	fn full_file(&mut self, file: &StepFile, source: &str) {
+		let added = file.diff.diffs.added_lines();
+		let mut fence = self.md.fence("diff");
+		for (no, line) in source.lines().enumerate() {
+			let marker = if added.binary_search(&(no as u64 + 1)).is_ok() { '+' } else { ' ' };
+			fence.append(format!("{marker}{line}\n"));
+		}
+	}
+
+	/// Collapsible block with complete file after the diff
+	fn full_source(&mut self, file: &StepFile, source: &str) {
+		self.close_hunks();
+		let path = file.path();
+		self.md.append("<details>\n<summary>Full source of <code>")
+			.append(escape(path))
+			.append("</code></summary>\n\n")
+			.code_block(language(path), source)
+			.append("</details>\n\n");
+	}
+
+	fn end_file(&mut self, _step: &Step, _file: &StepFile) {
+		self.close_hunks();
+	}
+
+	fn end_document(&mut self) -> String {
+		if self.config.toc {
+			// steps and their files, document title is left out
+			self.md.insert_toc(2..=self.level + 1);
+		}
+		std::mem::take(&mut self.md).build()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::Config;
+	use crate::generator::{render_document, MarkdownRenderer, Step, StepFile};
+	use crate::git::{ChangeKind, DiffInfo, DiffOptions, GitLog, Patch};
+
+	/// Step changing `m.rs`, complete file is attached when `source` is set
+	fn step(old: &str, new: &str, source: bool) -> Step {
+		let mut diff = DiffInfo::new("diff --git a/m.rs b/m.rs".to_string());
+		diff.kind = ChangeKind::Modified;
+		diff.new_path = Some("m.rs".to_string());
+		diff.diffs = Patch::diff(old, new, &DiffOptions::default());
+		Step {
+			no: 1,
+			log: GitLog::default(),
+			title: "Rename *call*".to_string(),
+			body: "Call `b` instead.".to_string(),
+			files: vec![StepFile { diff, asset: None, source: source.then(|| new.to_string()) }],
+			part: None,
+		}
+	}
+
+	#[test]
+	fn test_render_markdown() {
+		let (old, new) = ("fn main() {\n    a();\n}\n", "fn main() {\n    b();\n}\n");
+		let steps = [step(old, new, false)];
+		let mut config = Config { title: Some("Demo".to_string()), ..Config::default() };
+		let doc = render_document(&mut MarkdownRenderer::new(&config), &config, &steps, &steps);
+		assert_eq!(doc, "# Demo\n\n## Step 1: Rename \\*call\\*\n\nCall `b` instead.\n\n### `m.rs`\n\n\
+			```diff\n@@ -1,3 +1,3 @@\n fn main() {\n-    a();\n+    b();\n }\n```\n\n");
+
+		config.word_diff = true;
+		let steps = [step(old, new, true)];
+		let doc = render_document(&mut MarkdownRenderer::new(&config), &config, &steps, &steps);
+		assert!(doc.contains("```\n@@ -1,3 +1,3 @@\nfn main() {\n    [-a-]{+b+}();\n}\n```\n\n<details>"));
+		assert!(doc.ends_with("```rust\nfn main() {\n    b();\n}\n```\n\n</details>\n\n"));
+	}
+}
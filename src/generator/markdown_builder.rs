@@ -1,14 +1,78 @@
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut, RangeInclusive};
+
+use crate::generator::escape;
+
 /// A helper provided method to create markdown
 #[derive(Default)]
 pub struct MarkdownBuilder {
 	inner: String,
+	/// Level and start of text of every heading, for table of contents
+	headings: Vec<(usize, usize)>,
+	/// End of document title written by [Self::title]
+	title_end: Option<usize>,
+}
+
+/// Line replaced by table of contents
+pub const TOC_PLACEHOLDER: &str = "<!-- toc -->";
+
+/// Scoped element, content is written through this guard and element is closed when it is dropped
+pub struct MarkdownCloseTag<'a>(&'a mut MarkdownBuilder, Close);
+
+/// How element is closed
+enum Close {
+	/// Append closing text
+	Text(&'static str),
+	/// Surround content written after `start` with fences longer than any backtick run in it
+	Fence { start: usize, info: String },
+	/// Prefix lines written after `start`, `first` for the first line and `rest` for the others,
+	/// followed by blank line if `block` is set
+	Prefix { start: usize, first: String, rest: &'static str, block: bool },
+}
+
+impl Deref for MarkdownCloseTag<'_> {
+	type Target = MarkdownBuilder;
+
+	fn deref(&self) -> &Self::Target {
+		self.0
+	}
 }
 
-struct MarkdownCloseTag<'a>(&'a mut MarkdownBuilder, &'static str);
+impl DerefMut for MarkdownCloseTag<'_> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.0
+	}
+}
 
 impl<'a> Drop for MarkdownCloseTag<'a> {
 	fn drop(&mut self) {
-		self.0.append(self.1);
+		let inner = &mut self.0.inner;
+		match &self.1 {
+			Close::Text(text) => inner.push_str(text),
+			Close::Fence { start, info } => {
+				if !inner.ends_with('\n') && inner.len() > *start {
+					inner.push('\n');
+				}
+				let fence = "`".repeat(longest_run(&inner[*start..], '`').max(2) + 1);
+				inner.insert_str(*start, &format!("{fence}{info}\n"));
+				inner.push_str(&fence);
+				inner.push_str("\n\n");
+			}
+			Close::Prefix { start, first, rest, block } => {
+				let content = inner.split_off(*start);
+				let content = content.trim_end_matches('\n');
+				for (no, line) in content.split('\n').enumerate() {
+					let prefix = if no == 0 { first.as_str() } else { rest };
+					// no trailing whitespace on blank lines
+					inner.push_str(if line.is_empty() { prefix.trim_end() } else { prefix });
+					inner.push_str(line);
+					inner.push('\n');
+				}
+				if *block {
+					inner.push('\n');
+				}
+			}
+		}
 	}
 }
 
@@ -19,12 +83,17 @@ impl MarkdownBuilder {
 		self
 	}
 
-	/// Append text to markdown
+	/// Append trusted text to markdown as is, see [MarkdownBuilder::text] for untrusted text
 	pub fn append(&mut self, text: impl AsRef<str>) -> &mut Self {
 		self.inner.push_str(text.as_ref());
 		self
 	}
 
+	/// Append text with markdown special characters escaped so it is rendered literally
+	pub fn text(&mut self, text: impl AsRef<str>) -> &mut Self {
+		self.append(escape::text(text.as_ref()))
+	}
+
 	/// Append text with newline to markdown
 	#[inline]
 	pub fn appendln(&mut self, text: impl AsRef<str>) -> &mut Self {
@@ -41,13 +110,69 @@ impl MarkdownBuilder {
 			.append("  \n")
 	}
 
-	/// Append heading to markdown output as `{'#'*$level} `
+	/// Append heading to markdown output as `{'#'*$level} `, text until end of line is the heading
 	pub fn heading(&mut self, level: usize) -> &mut Self {
 		self.reserve(level + 1);
 		for _ in 0..level {
 			self.append("#");
 		}
-		self.append(" ")
+		self.append(" ");
+		self.headings.push((level, self.inner.len()));
+		self
+	}
+
+	/// Append level 1 heading as document title, table of contents without placeholder goes after it
+	pub fn title(&mut self, title: &str) -> &mut Self {
+		self.heading(1)
+			.text(title)
+			.append("\n\n");
+		self.title_end = Some(self.inner.len());
+		self
+	}
+
+	/// Insert nested list linking headings of `levels` at [TOC_PLACEHOLDER] line,
+	/// or at top of document after its [title](Self::title) when there is no placeholder
+	pub fn insert_toc(&mut self, levels: RangeInclusive<usize>) -> &mut Self {
+		let mut toc = String::new();
+		let mut seen = HashMap::new();
+		for &(level, start) in &self.headings {
+			let text = self.inner[start..].lines().next().unwrap_or_default().trim_end();
+			// every heading takes a slug so duplicates are numbered like GitHub does
+			let slug = slug(text);
+			let count = seen.entry(slug.clone()).or_insert(0);
+			let anchor = if *count == 0 { slug } else { format!("{slug}-{count}") };
+			*count += 1;
+			if levels.contains(&level) {
+				let indent = "  ".repeat(level - levels.start());
+				toc.push_str(&format!("{indent}- [{text}](#{anchor})\n"));
+			}
+		}
+		if toc.is_empty() {
+			return self;
+		}
+		let (at, replaced) = match self.placeholder() {
+			Some(at) => (at, TOC_PLACEHOLDER.len() + 1),
+			None => {
+				toc.push('\n');
+				(self.title_end.unwrap_or(0), 0)
+			}
+		};
+		self.inner.replace_range(at..at + replaced, &toc);
+		for start in self.headings.iter_mut().map(|it| &mut it.1).chain(&mut self.title_end) {
+			if *start > at {
+				*start = *start + toc.len() - replaced;
+			}
+		}
+		self
+	}
+
+	/// Start of first line consisting of [TOC_PLACEHOLDER]
+	fn placeholder(&self) -> Option<usize> {
+		self.inner.match_indices(TOC_PLACEHOLDER)
+			.map(|(at, _)| at)
+			.find(|&at| {
+				(at == 0 || self.inner[..at].ends_with('\n')) && self.inner[at + TOC_PLACEHOLDER.len()..].starts_with('\n')
+			})
 	}
 
 	/// Append link to markdown output as `[$text]($link)`
@@ -62,8 +187,208 @@ impl MarkdownBuilder {
 			.append(")")
 	}
 
+	/// Append link with escaped text and percent-encoded target
+	pub fn escaped_link(&mut self, text: impl AsRef<str>, link: impl AsRef<str>) -> &mut Self {
+		self.link(escape::link_text(text.as_ref()), escape::url(link.as_ref()))
+	}
+
+	/// Append image as `![$alt]($link)`
+	pub fn image(&mut self, alt: impl AsRef<str>, link: impl AsRef<str>) -> &mut Self {
+		self.append("!")
+			.link(alt, link)
+	}
+
+	/// Append image with escaped alt text and percent-encoded target
+	pub fn escaped_image(&mut self, alt: impl AsRef<str>, link: impl AsRef<str>) -> &mut Self {
+		self.append("!")
+			.escaped_link(alt, link)
+	}
+
+	/// Append `**`, content is bold until guard is dropped
+	pub fn bold(&mut self) -> MarkdownCloseTag<'_> {
+		self.append("**");
+		MarkdownCloseTag(self, Close::Text("**"))
+	}
+
+	/// Append `*`, content is italic until guard is dropped
+	pub fn italic(&mut self) -> MarkdownCloseTag<'_> {
+		self.append("*");
+		MarkdownCloseTag(self, Close::Text("*"))
+	}
+
+	/// Append inline code span, delimited by more backticks than any run inside `code`
+	pub fn code(&mut self, code: impl AsRef<str>) -> &mut Self {
+		let code = code.as_ref();
+		let ticks = "`".repeat(longest_run(code, '`') + 1);
+		// space keep backtick at either end apart from delimiter
+		let pad = if code.starts_with('`') || code.ends_with('`') { " " } else { "" };
+		self.append(&ticks)
+			.append(pad)
+			.append(code)
+			.append(pad)
+			.append(&ticks)
+	}
+
+	/// Start fenced code block with `info` string (such as `diff` or `rust`),
+	/// fence is chosen when guard is dropped so it is longer than any backtick run in content
+	pub fn fence(&mut self, info: impl Into<String>) -> MarkdownCloseTag<'_> {
+		let start = self.inner.len();
+		MarkdownCloseTag(self, Close::Fence { start, info: info.into() })
+	}
+
+	/// Append fenced code block of `content` followed by blank line
+	pub fn code_block(&mut self, info: impl Into<String>, content: impl AsRef<str>) -> &mut Self {
+		self.fence(info)
+			.append(content);
+		self
+	}
+
+	/// Start block quote, every line written before guard is dropped is prefixed with `> `,
+	/// followed by blank line
+	pub fn blockquote(&mut self) -> MarkdownCloseTag<'_> {
+		let start = self.inner.len();
+		MarkdownCloseTag(self, Close::Prefix { start, first: "> ".to_string(), rest: "> ", block: true })
+	}
+
+	/// Start unordered list item, following lines written before guard is dropped are indented under it,
+	/// list must be followed by blank line
+	pub fn bullet(&mut self) -> MarkdownCloseTag<'_> {
+		let start = self.inner.len();
+		MarkdownCloseTag(self, Close::Prefix { start, first: "- ".to_string(), rest: "  ", block: false })
+	}
+
+	/// Start ordered list item `no`, following lines written before guard is dropped are indented under it
+	pub fn numbered(&mut self, no: usize) -> MarkdownCloseTag<'_> {
+		let start = self.inner.len();
+		let first = format!("{no}. ");
+		let rest = &"      "[..first.len().min(6)];
+		MarkdownCloseTag(self, Close::Prefix { start, first, rest, block: false })
+	}
+
+	/// Append table with header row followed by blank line, `|` and newlines in cells are escaped
+	pub fn table<H: AsRef<str>, C: AsRef<str>>(&mut self, headers: &[H], rows: &[Vec<C>]) -> &mut Self {
+		let cell = |text: &str| text.replace('|', "\\|").replace('\n', "<br>");
+		let row = |md: &mut Self, cells: Vec<String>| {
+			md.append("|");
+			for cell in cells {
+				md.append(" ").append(cell).append(" |");
+			}
+			md.append("\n");
+		};
+		row(self, headers.iter().map(|it| cell(it.as_ref())).collect());
+		row(self, headers.iter().map(|_| "---".to_string()).collect());
+		for cells in rows {
+			// missing cells are left empty
+			row(self, (0..headers.len()).map(|at| cells.get(at).map(|it| cell(it.as_ref())).unwrap_or_default()).collect());
+		}
+		self.append("\n")
+	}
+
+	/// Append horizontal rule followed by blank line
+	pub fn hr(&mut self) -> &mut Self {
+		self.append("---\n\n")
+	}
+
 	/// Return markdown content and drop a builder
 	pub fn build(self) -> String {
 		self.inner
 	}
-}
\ No newline at end of file
+}
+
+/// GitHub anchor of heading `text`: lowercase, punctuation other than `-` and `_` removed and spaces replaced by `-`
+fn slug(text: &str) -> String {
+	text.chars()
+		.filter(|it| it.is_alphanumeric() || matches!(it, ' ' | '-' | '_'))
+		.flat_map(char::to_lowercase)
+		.map(|it| if it == ' ' { '-' } else { it })
+		.collect()
+}
+
+/// Length of longest run of `ch` in `text`
+fn longest_run(text: &str, ch: char) -> usize {
+	let (mut longest, mut run) = (0, 0);
+	for it in text.chars() {
+		run = if it == ch { run + 1 } else { 0 };
+		longest = longest.max(run);
+	}
+	longest
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::generator::MarkdownBuilder;
+
+	#[test]
+	fn test_fence() {
+		let mut md = MarkdownBuilder::default();
+		md.code_block("rust", "fn main() {}");
+		md.code_block("markdown", "```rust\n````\n");
+		assert_eq!(md.build(), "```rust\nfn main() {}\n```\n\n`````markdown\n```rust\n````\n`````\n\n");
+	}
+
+	#[test]
+	fn test_inline() {
+		let mut md = MarkdownBuilder::default();
+		md.bold().append("bold ").italic().append("both");
+		md.append(" ")
+			.code("a`b")
+			.append(" ")
+			.code("`")
+			.append(" ")
+			.image("logo", "assets/logo.png");
+		assert_eq!(md.build(), "**bold *both*** ``a`b`` `` ` `` ![logo](assets/logo.png)");
+	}
+
+	#[test]
+	fn test_escaped() {
+		let mut md = MarkdownBuilder::default();
+		md.heading(2)
+			.text("Step 1: use *args [wip]")
+			.append("\n")
+			.escaped_link("a_b", "docs/a b.md")
+			.append(" ")
+			.escaped_image("logo", "assets/logo (old).png");
+		assert_eq!(md.build(), "## Step 1: use \\*args \\[wip\\]\n[a\\_b](docs/a%20b.md) ![logo](assets/logo%20%28old%29.png)");
+	}
+
+	#[test]
+	fn test_toc() {
+		let mut md = MarkdownBuilder::default();
+		md.title("Demo");
+		for (level, text) in [(2, "Step 1: Add `m.rs`"), (3, "`m.rs`"), (2, "Step 2: Use \\*args"), (3, "`m.rs`")] {
+			md.heading(level).append(text).append("\n\n");
+		}
+		md.insert_toc(2..=3);
+		assert_eq!(md.build(), "# Demo\n\n- [Step 1: Add `m.rs`](#step-1-add-mrs)\n  - [`m.rs`](#mrs)\n\
+			- [Step 2: Use \\*args](#step-2-use-args)\n  - [`m.rs`](#mrs-1)\n\n\
+			## Step 1: Add `m.rs`\n\n### `m.rs`\n\n## Step 2: Use \\*args\n\n### `m.rs`\n\n");
+
+		let mut md = MarkdownBuilder::default();
+		md.append("intro\n<!-- toc -->\n\n").heading(2).append("A b\n");
+		md.insert_toc(2..=2);
+		assert_eq!(md.build(), "intro\n- [A b](#a-b)\n\n## A b\n");
+
+		// level 1 heading which is not the title is listed below the table of contents
+		let mut md = MarkdownBuilder::default();
+		md.heading(1).append("v1\n\nIntro\n\n").heading(2).append("A\n");
+		md.insert_toc(1..=2);
+		assert_eq!(md.build(), "- [v1](#v1)\n  - [A](#a)\n\n# v1\n\nIntro\n\n## A\n");
+	}
+
+	#[test]
+	fn test_blocks() {
+		let mut md = MarkdownBuilder::default();
+		{
+			let mut quote = md.blockquote();
+			quote.append("note\n\n");
+			quote.fence("sh").append("ls");
+		}
+		md.bullet().append("one\nmore");
+		md.numbered(10).append("ten");
+		md.append("\n")
+			.table(&["a", "b|c"], &[vec!["1"], vec!["2", "x\ny"]])
+			.hr();
+		assert_eq!(md.build(), "> note\n>\n> ```sh\n> ls\n> ```\n\n- one\n  more\n10. ten\n\n\
+			| a | b\\|c |\n| --- | --- |\n| 1 |  |\n| 2 | x<br>y |\n\n---\n\n");
+	}
+}
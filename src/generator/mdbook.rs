@@ -0,0 +1,102 @@
+use crate::config::Config;
+use crate::generator::{MarkdownBuilder, Part, Step};
+
+/// Directory of chapters relative to book root
+pub(crate) const SOURCE_DIR: &str = "src";
+
+/// Chapter file of step `no` inside [SOURCE_DIR]
+pub(crate) fn chapter_name(no: usize) -> String {
+	format!("step-{no}.md")
+}
+
+/// Intro chapter of part starting at step `no` inside [SOURCE_DIR]
+pub(crate) fn part_name(no: usize) -> String {
+	format!("part-{no}.md")
+}
+
+/// Intro chapter of part with title and intro
+pub(crate) fn part_chapter(part: &Part) -> Option<String> {
+	let (Some(title), Some(intro)) = (&part.title, &part.intro) else {
+		return None;
+	};
+	let mut md = MarkdownBuilder::default();
+	md.heading(1)
+		.text(title)
+		.append("\n\n")
+		.append(intro)
+		.append("\n");
+	Some(md.build())
+}
+
+/// `book.toml` with document title
+pub(crate) fn book_toml(config: &Config) -> String {
+	let mut book = String::from("[book]\n");
+	if let Some(title) = &config.title {
+		book.push_str(&format!("title = {}\n", toml::Value::String(title.clone())));
+	}
+	book.push_str(&format!("src = \"{SOURCE_DIR}\"\n"));
+	book
+}
+
+/// `SUMMARY.md` linking chapter of every step, a part starts with its title or a separator when untitled,
+/// steps are nested under intro chapter of their part
+pub(crate) fn summary(steps: &[Step]) -> String {
+	let mut md = MarkdownBuilder::default();
+	md.heading(1).append("Summary\n\n");
+	let mut nested = false;
+	for (at, step) in steps.iter().enumerate() {
+		if let Some(part) = &step.part {
+			if at > 0 {
+				md.append("\n");
+			}
+			match &part.title {
+				Some(title) => md.heading(1).text(title).append("\n\n"),
+				None => md.hr(),
+			};
+			nested = part_chapter(part).is_some();
+			if let (true, Some(title)) = (nested, &part.title) {
+				md.append("- ")
+					.escaped_link(title, part_name(step.no))
+					.append("\n");
+			}
+		}
+		md.append(if nested { "  - " } else { "- " })
+			.escaped_link(format!("Step {}: {}", step.no, step.title), chapter_name(step.no))
+			.append("\n");
+	}
+	md.build()
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::Config;
+	use crate::generator::mdbook::{book_toml, part_chapter, summary};
+	use crate::generator::{Part, Step};
+	use crate::git::GitLog;
+
+	fn step(no: usize, title: &str, part: Option<Option<&str>>) -> Step {
+		Step {
+			no,
+			log: GitLog::default(),
+			title: title.to_string(),
+			body: String::new(),
+			files: vec![],
+			part: part.map(|it| Part { title: it.map(str::to_string), intro: None }),
+		}
+	}
+
+	#[test]
+	fn test_summary() {
+		let steps = [step(1, "Setup", Some(Some("v1"))), step(2, "Add *main*", None), step(3, "Wip", Some(None))];
+		assert_eq!(summary(&steps), "# Summary\n\n# v1\n\n- [Step 1: Setup](step-1.md)\n\
+			- [Step 2: Add \\*main\\*](step-2.md)\n\n---\n\n- [Step 3: Wip](step-3.md)\n");
+		let mut steps = steps;
+		steps[0].part = Some(Part { title: Some("v1".to_string()), intro: Some("Basics.".to_string()) });
+		assert_eq!(summary(&steps), "# Summary\n\n# v1\n\n- [v1](part-1.md)\n  - [Step 1: Setup](step-1.md)\n  - \
+			[Step 2: Add \\*main\\*](step-2.md)\n\n---\n\n- [Step 3: Wip](step-3.md)\n");
+		assert_eq!(steps[0].part.as_ref().and_then(part_chapter).as_deref(), Some("# v1\n\nBasics.\n"));
+
+		let config = Config { title: Some("Say \"hi\"".to_string()), ..Config::default() };
+		assert_eq!(book_toml(&config), "[book]\ntitle = 'Say \"hi\"'\nsrc = \"src\"\n");
+	}
+}
@@ -1 +1,20 @@
-mod markdown_builder;
\ No newline at end of file
+pub use asciidoc::AsciidocRenderer;
+pub use html::HtmlRenderer;
+pub use json::SCHEMA_VERSION;
+pub use markdown::MarkdownRenderer;
+pub use markdown_builder::MarkdownBuilder;
+pub use renderer::{render_document, DocumentRenderer, Part, Step, StepFile};
+pub use snapshot::{Snapshot, Snapshots};
+pub use tutorial::TutorialGenerator;
+
+pub mod escape;
+mod asciidoc;
+mod highlight;
+mod html;
+mod json;
+mod markdown;
+mod markdown_builder;
+mod mdbook;
+mod renderer;
+mod snapshot;
+mod tutorial;
@@ -0,0 +1,117 @@
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::git::{BinaryInfo, DiffInfo, GitLog, PatchInfo};
+
+/// Commit with its changed files, ready to be rendered
+#[derive(Serialize)]
+pub struct Step {
+	/// Step number starting from 1
+	pub no: usize,
+	#[serde(rename = "commit")]
+	pub log: GitLog,
+	/// Heading after step overrides
+	pub title: String,
+	/// Prose after step overrides, may be empty
+	pub body: String,
+	pub files: Vec<StepFile>,
+	/// Part of the document starting at this step
+	pub part: Option<Part>,
+}
+
+/// Group of consecutive steps, from a configured section or ended by an annotated tag
+#[derive(Serialize)]
+pub struct Part {
+	/// Section title, branch or tag name, `None` for steps after the last tag
+	pub title: Option<String>,
+	/// Message of annotated tag, shown before first step of the part
+	pub intro: Option<String>,
+}
+
+/// Changed file of a step with data read from repository before rendering
+#[derive(Serialize)]
+pub struct StepFile {
+	#[serde(flatten)]
+	pub diff: DiffInfo,
+	/// Relative link of extracted image
+	pub asset: Option<String>,
+	/// Complete file after the step
+	pub source: Option<String>,
+}
+
+impl StepFile {
+	pub fn path(&self) -> &str {
+		self.diff.path()
+	}
+}
+
+/// Output format, receives parts of document in order from [render_document]
+pub trait DocumentRenderer {
+	/// Start document, `steps` are every step of the document even if only some are rendered
+	fn begin_document(&mut self, title: Option<&str>, steps: &[Step]);
+
+	/// Part starting before next step
+	fn begin_part(&mut self, _part: &Part) {}
+
+	fn begin_step(&mut self, step: &Step);
+
+	/// Commit message or prose override of current step, never empty
+	fn prose(&mut self, step: &Step, text: &str);
+
+	/// Heading of changed file
+	fn begin_file(&mut self, step: &Step, file: &StepFile);
+
+	/// Binary file summary and extracted image
+	fn binary(&mut self, file: &StepFile, binary: &BinaryInfo);
+
+	/// Single hunk of text file, `index` start from 0 for every file
+	fn hunk(&mut self, file: &StepFile, hunk: &PatchInfo<'_>, index: usize);
+
+	/// Complete file with lines added by this step marked, rendered instead of hunks
+	fn full_file(&mut self, file: &StepFile, source: &str);
+
+	/// Complete file after its hunks
+	fn full_source(&mut self, file: &StepFile, source: &str);
+
+	fn end_file(&mut self, _step: &Step, _file: &StepFile) {}
+
+	fn end_step(&mut self, _step: &Step) {}
+
+	/// Finish document and return its content
+	fn end_document(&mut self) -> String;
+}
+
+/// Walk `shown` steps and feed them to `renderer`, `steps` are every step of the document
+pub fn render_document(renderer: &mut dyn DocumentRenderer, config: &Config, steps: &[Step], shown: &[Step]) -> String {
+	renderer.begin_document(config.title.as_deref(), steps);
+	for step in shown {
+		if let Some(part) = &step.part {
+			renderer.begin_part(part);
+		}
+		renderer.begin_step(step);
+		if !step.body.is_empty() {
+			renderer.prose(step, &step.body);
+		}
+		for file in &step.files {
+			renderer.begin_file(step, file);
+			match (&file.diff.binary, &file.source) {
+				(Some(binary), _) => renderer.binary(file, binary),
+				(None, Some(source)) if config.is_full_file(file.path()) => renderer.full_file(file, source),
+				(None, source) => {
+					let patch = &file.diff.diffs;
+					for index in 0..patch.patches() {
+						if let Some(hunk) = patch.get_patch(index) {
+							renderer.hunk(file, &hunk, index);
+						}
+					}
+					if let Some(source) = source {
+						renderer.full_source(file, source);
+					}
+				}
+			}
+			renderer.end_file(step, file);
+		}
+		renderer.end_step(step);
+	}
+	renderer.end_document()
+}
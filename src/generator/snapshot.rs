@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::error::{ApplyError, Result, StepdocsError};
+use crate::git::{ChangeKind, DiffInfo, GitLog, GitRepository};
+
+/// Content of text files rebuilt by applying patch of every step on top of previous steps
+#[derive(Debug, Default, Clone)]
+pub struct Snapshot {
+	files: HashMap<String, String>,
+}
+
+impl Snapshot {
+	/// Content of `path` after last applied step, `None` if file was never changed as text
+	pub fn get(&self, path: &str) -> Option<&str> {
+		self.files.get(path).map(String::as_str)
+	}
+
+	/// Apply changes made by `commit`, file content before first change is read from repository
+	pub async fn apply(&mut self, repo: &GitRepository, commit: &str, diffs: &[DiffInfo]) -> Result<()> {
+		for diff in diffs {
+			let old_path = diff.old_path.as_deref();
+			let tracked = old_path.and_then(|it| self.files.get(it)).cloned();
+			if diff.kind == ChangeKind::Renamed || diff.new_path.is_none() {
+				old_path.map(|it| self.files.remove(it));
+			}
+			let Some(new_path) = diff.new_path.as_deref() else {
+				continue;
+			};
+			let base = match (tracked, diff.blob_ids().0) {
+				_ if diff.is_binary() => None,
+				(Some(content), _) => Some(content),
+				_ if diff.kind == ChangeKind::Added => Some(String::new()),
+				(None, Some(id)) => Some(String::from_utf8_lossy(&repo.read_blob(id).await?).into_owned()),
+				// pure rename without blob ids, content is unknown
+				(None, None) => None,
+			};
+			let Some(base) = base else {
+				self.files.remove(new_path);
+				continue;
+			};
+			let content = diff.diffs.apply(&base).map_err(|source| StepdocsError::Apply {
+				commit: commit.to_string(),
+				file: new_path.to_string(),
+				source,
+			})?;
+			self.files.insert(new_path.to_string(), content);
+		}
+		Ok(())
+	}
+
+	/// Compare rebuilt content of files changed by `commit` with blobs recorded in it
+	pub async fn verify(&self, repo: &GitRepository, commit: &str, diffs: &[DiffInfo]) -> Result<()> {
+		for diff in diffs {
+			let (Some(path), (_, Some(id))) = (diff.new_path.as_deref(), diff.blob_ids()) else {
+				continue;
+			};
+			let Some(content) = self.get(path) else {
+				continue;
+			};
+			if String::from_utf8_lossy(&repo.read_blob(id).await?) != content {
+				return Err(StepdocsError::Apply {
+					commit: commit.to_string(),
+					file: path.to_string(),
+					source: ApplyError::Mismatch(id.to_string()),
+				});
+			}
+		}
+		Ok(())
+	}
+}
+
+/// [Snapshot] after every applied commit, so a commit is applied on top of its first parent
+/// rather than whichever commit was applied before it
+#[derive(Debug, Default)]
+pub struct Snapshots {
+	commits: HashMap<String, Snapshot>,
+}
+
+impl Snapshots {
+	/// Apply changes made by `log` against its first parent, starting from empty snapshot
+	/// when first parent was not applied, and return the resulting snapshot
+	pub async fn apply(&mut self, repo: &GitRepository, log: &GitLog, diffs: &[DiffInfo]) -> Result<&Snapshot> {
+		let mut snapshot = log.parents.first()
+			.and_then(|it| self.commits.get(it))
+			.cloned()
+			.unwrap_or_default();
+		snapshot.apply(repo, &log.hash, diffs).await?;
+		Ok(self.commits.entry(log.hash.clone()).insert_entry(snapshot).into_mut())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::generator::snapshot::Snapshots;
+	use crate::git::fixture::TestRepo;
+	use crate::git::{GitBackend, GitRepository, LogOptions, LogOrder};
+	use crate::util::iter::AsyncIterator;
+
+	#[tokio::test]
+	async fn test_apply_merge() {
+		let test = TestRepo::new("snapshot-merge");
+		test.write("f", "base\n");
+		test.commit("Base");
+		test.git(&["checkout", "-q", "-b", "side"]);
+		test.write("f", "side\n");
+		test.commit("Side");
+		test.git(&["checkout", "-q", "main"]);
+		test.write("f", "main\n");
+		test.commit("Main");
+		test.git(&["merge", "-q", "-s", "ours", "--no-commit", "side"]);
+		test.write("f", "merged\n");
+		test.commit("Merge side");
+
+		let repo = GitRepository::open(test.path(), GitBackend::Process).unwrap();
+		let options = LogOptions::new().range("main").order(LogOrder::Topo).reverse(true);
+		let logs = repo.log(&options).unwrap().collect().await.unwrap();
+		// side is applied after main, but on top of base
+		assert_eq!(logs.iter().map(|it| it.subject.as_str()).collect::<Vec<_>>(), ["Base", "Main", "Side", "Merge side"]);
+		let mut snapshots = Snapshots::default();
+		let mut contents = Vec::new();
+		for log in &logs {
+			let diffs = repo.show(&log.hash).unwrap().collect().await.unwrap();
+			let snapshot = snapshots.apply(&repo, log, &diffs).await.unwrap();
+			snapshot.verify(&repo, &log.hash, &diffs).await.unwrap();
+			contents.push(snapshot.get("f").unwrap().to_string());
+		}
+		assert_eq!(contents, ["base\n", "main\n", "side\n", "merged\n"]);
+	}
+}
@@ -0,0 +1,344 @@
+use std::path::Path;
+
+use crate::config::{Config, ExportFormat, OutputFormat};
+use crate::error::Result;
+use crate::generator::html::{page_name, HtmlRenderer};
+use crate::generator::{json, mdbook};
+use crate::generator::{render_document, AsciidocRenderer, DocumentRenderer, MarkdownRenderer, Part, Step, StepFile};
+use crate::git::{BinaryInfo, ChangeKind, DiffInfo, GitLog, GitRepository, LogOptions, LogOrder};
+use crate::util::iter::AsyncIterator;
+
+/// Walk commits in a revision range and render them as a step by step tutorial
+pub struct TutorialGenerator<'a> {
+	repo: &'a GitRepository,
+	config: Config,
+}
+
+impl<'a> TutorialGenerator<'a> {
+	pub fn new(repo: &'a GitRepository) -> Self {
+		Self::with_config(repo, Config::default())
+	}
+
+	pub fn with_config(repo: &'a GitRepository, config: Config) -> Self {
+		Self { repo, config }
+	}
+
+	pub fn config(&self) -> &Config {
+		&self.config
+	}
+
+	/// Revision range to walk, every ref is used when not set
+	pub fn range(&mut self, range: impl Into<String>) -> &mut Self {
+		self.config.range = Some(range.into());
+		self
+	}
+
+	/// Title of generated document
+	pub fn title(&mut self, title: impl Into<String>) -> &mut Self {
+		self.config.title = Some(title.into());
+		self
+	}
+
+	/// Commits in configured range or branches (oldest first) without skipped steps
+	pub async fn steps(&self) -> Result<Vec<GitLog>> {
+		Ok(self.walk().await?.into_iter().map(|it| it.0).collect())
+	}
+
+	/// Commits without skipped steps, every configured branch starts a part at its first commit
+	async fn walk(&self) -> Result<Vec<(GitLog, Option<Part>)>> {
+		let mut logs = Vec::new();
+		if self.config.branches.is_empty() {
+			let options = self.log_options(self.config.range.as_deref()).first_parent(self.config.first_parent);
+			let all: Vec<_> = self.repo.log(&options)?.collect().await?;
+			logs.extend(all.into_iter().map(|it| (it, None)));
+		}
+		let branches = &self.config.branches;
+		for (at, branch) in branches.iter().enumerate() {
+			let range = match at {
+				0 => branch.clone(),
+				_ => format!("{}..{branch}", branches[at - 1]),
+			};
+			let mut part = Some(Part { title: Some(branch.clone()), intro: None });
+			let branch_logs: Vec<_> = self.repo.log(&self.log_options(Some(&range)).first_parent(true))?.collect().await?;
+			logs.extend(branch_logs.into_iter().map(|it| (it, part.take())));
+		}
+		// part of skipped step starts at next step instead
+		let mut steps = Vec::with_capacity(logs.len());
+		let mut pending = None;
+		for (log, part) in logs {
+			let part = part.or(pending.take());
+			if self.config.step(&log.hash).is_some_and(|it| it.skip) {
+				pending = part;
+				continue;
+			}
+			steps.push((log, part));
+		}
+		Ok(steps)
+	}
+
+	/// Commits of `range` (every ref when `None`) in teaching order, oldest first and parents before children
+	fn log_options(&self, range: Option<&str>) -> LogOptions {
+		let options = LogOptions::new().order(LogOrder::Topo).reverse(true);
+		match range {
+			Some(range) => options.range(range),
+			None => options,
+		}
+	}
+
+	/// Generate document in configured format from every step
+	pub async fn generate(&self) -> Result<String> {
+		let steps = self.load(None).await?;
+		Ok(self.render(&steps))
+	}
+
+	/// Export every step in machine readable `format`, images are not extracted
+	pub async fn export(&self, format: ExportFormat) -> Result<String> {
+		let steps = self.load(None).await?;
+		match format {
+			ExportFormat::Json => json::to_json(&self.config, &steps),
+		}
+	}
+
+	/// Generate document from every step with custom output format
+	pub async fn render_with(&self, renderer: &mut dyn DocumentRenderer) -> Result<String> {
+		let steps = self.load(None).await?;
+		Ok(render_document(renderer, &self.config, &steps, &steps))
+	}
+
+	/// Generate document and write it to `path`,
+	/// images are extracted next to it when `embed-images` is enabled,
+	/// with `split-steps` html pages of every step are written next to it,
+	/// mdBook format write a book directory at `path`
+	pub async fn write_to(&self, path: impl AsRef<Path>) -> Result<()> {
+		let path = path.as_ref();
+		if self.config.format == OutputFormat::Mdbook {
+			return self.write_book(path).await;
+		}
+		let out_dir = path.parent().unwrap_or(Path::new("."));
+		tokio::fs::create_dir_all(out_dir).await?;
+		let steps = self.load(Some(out_dir)).await?;
+		if self.config.format == OutputFormat::Html && self.config.split_steps {
+			// index page lists steps without rendering them
+			let index = render_document(&mut HtmlRenderer::page(&self.config, 0), &self.config, &steps, &[]);
+			tokio::fs::write(path, index).await?;
+			for step in &steps {
+				let mut renderer = HtmlRenderer::page(&self.config, step.no);
+				let page = render_document(&mut renderer, &self.config, &steps, std::slice::from_ref(step));
+				tokio::fs::write(out_dir.join(page_name(step.no)), page).await?;
+			}
+			return Ok(());
+		}
+		tokio::fs::write(path, self.render(&steps)).await?;
+		Ok(())
+	}
+
+	/// Write `book.toml` into `dir`, `SUMMARY.md`, chapters and images into its source directory
+	async fn write_book(&self, dir: &Path) -> Result<()> {
+		let src = dir.join(mdbook::SOURCE_DIR);
+		tokio::fs::create_dir_all(&src).await?;
+		let steps = self.load(Some(&src)).await?;
+		tokio::fs::write(dir.join("book.toml"), mdbook::book_toml(&self.config)).await?;
+		tokio::fs::write(src.join("SUMMARY.md"), mdbook::summary(&steps)).await?;
+		for step in &steps {
+			if let Some(chapter) = step.part.as_ref().and_then(mdbook::part_chapter) {
+				tokio::fs::write(src.join(mdbook::part_name(step.no)), chapter).await?;
+			}
+			let mut renderer = MarkdownRenderer::chapter(&self.config);
+			let chapter = render_document(&mut renderer, &self.config, &steps, std::slice::from_ref(step));
+			tokio::fs::write(src.join(mdbook::chapter_name(step.no)), chapter).await?;
+		}
+		Ok(())
+	}
+
+	/// Read changes of every step and prepare files for rendering
+	async fn load(&self, out_dir: Option<&Path>) -> Result<Vec<Step>> {
+		let logs = self.walk().await?;
+		let mut steps = Vec::with_capacity(logs.len());
+		for (no, (log, part)) in logs.into_iter().enumerate() {
+			let mut diffs = self.repo.show(&log.hash)?
+				.collect()
+				.await?;
+			diffs.retain(|it| self.config.is_included(&log.hash, it.path()));
+			let mut files = Vec::with_capacity(diffs.len());
+			for mut diff in diffs {
+				let asset = self.prepare_binary(&mut diff, out_dir).await?;
+				let source = self.prepare_source(&diff, &log.hash).await?;
+				files.push(StepFile { diff, asset, source });
+			}
+			let over = self.config.step(&log.hash);
+			let title = over.and_then(|it| it.title.as_deref())
+				.or(log.trailer("Step-Title"))
+				.unwrap_or(&log.subject)
+				.to_string();
+			let body = over.and_then(|it| it.prose.as_deref()).unwrap_or(&log.body).trim().to_string();
+			steps.push(Step { no: no + 1, log, title, body, files, part });
+		}
+		if self.config.branches.is_empty() {
+			self.group_parts(&mut steps).await?;
+		}
+		Ok(steps)
+	}
+
+	/// Start parts at configured sections, otherwise end a part at every step with annotated tag
+	/// and use message of annotated tag as its intro
+	async fn group_parts(&self, steps: &mut [Step]) -> Result<()> {
+		if !self.config.sections.is_empty() {
+			for step in steps {
+				step.part = self.config.section(&step.log.hash).map(|it| Part { title: Some(it.title.clone()), intro: None });
+			}
+			return Ok(());
+		}
+		// lightweight tags mark commits for other purposes, like bookmarks and releases
+		let tags = self.repo.tags().await?.into_iter().filter(|it| it.message.is_some()).collect::<Vec<_>>();
+		let tag = |step: &Step| tags.iter().find(|it| it.commit == step.log.hash);
+		if !steps.iter().any(|it| tag(it).is_some()) {
+			return Ok(());
+		}
+		let mut start = 0;
+		for end in 0..steps.len() {
+			let found = tag(&steps[end]);
+			if found.is_some() || end + 1 == steps.len() {
+				steps[start].part = Some(Part {
+					title: found.map(|it| it.name.clone()),
+					intro: found.and_then(|it| it.message.clone()).filter(|it| !it.is_empty()),
+				});
+				start = end + 1;
+			}
+		}
+		Ok(())
+	}
+
+	fn render(&self, steps: &[Step]) -> String {
+		match self.config.format {
+			// single markdown document when book is not written to a directory
+			OutputFormat::Markdown | OutputFormat::Mdbook => render_document(&mut MarkdownRenderer::new(&self.config), &self.config, steps, steps),
+			OutputFormat::Html => render_document(&mut HtmlRenderer::new(&self.config), &self.config, steps, steps),
+			OutputFormat::Asciidoc => render_document(&mut AsciidocRenderer::new(&self.config), &self.config, steps, steps),
+		}
+	}
+
+	/// Fill missing blob sizes of binary file and extract image into `out_dir/assets`,
+	/// return relative link of extracted image
+	async fn prepare_binary(&self, diff: &mut DiffInfo, out_dir: Option<&Path>) -> Result<Option<String>> {
+		let path = diff.path().to_string();
+		let Some(binary) = &mut diff.binary else {
+			return Ok(None);
+		};
+		if let (None, Some(id)) = (binary.old_size, &binary.old_id) {
+			binary.old_size = self.repo.object_size(id).await.ok();
+		}
+		if let (None, Some(id)) = (binary.new_size, &binary.new_id) {
+			binary.new_size = self.repo.object_size(id).await.ok();
+		}
+		let (Some(out_dir), Some(id)) = (out_dir, &binary.new_id) else {
+			return Ok(None);
+		};
+		if !self.config.embed_images || !is_image(&path) {
+			return Ok(None);
+		}
+		let name = path.rsplit('/').next().unwrap_or(&path);
+		let link = format!("{ASSET_DIR}/{}-{}", &id[..12.min(id.len())], name);
+		let target = out_dir.join(&link);
+		if !target.exists() {
+			let content = self.repo.read_blob(id).await?;
+			tokio::fs::create_dir_all(out_dir.join(ASSET_DIR)).await?;
+			tokio::fs::write(target, content).await?;
+		}
+		Ok(Some(link))
+	}
+
+	/// Read complete file after `commit` when it is rendered as full file or full source
+	async fn prepare_source(&self, diff: &DiffInfo, commit: &str) -> Result<Option<String>> {
+		let Some(path) = diff.new_path.as_deref() else {
+			return Ok(None);
+		};
+		if diff.is_binary() || !(self.config.full_source || self.config.is_full_file(path)) {
+			return Ok(None);
+		}
+		let content = self.repo.cat_file(commit, path).await?;
+		Ok(Some(String::from_utf8_lossy(&content).into_owned()))
+	}
+}
+
+/// Directory of extracted images relative to generated document
+const ASSET_DIR: &str = "assets";
+
+fn is_image(path: &str) -> bool {
+	let ext = path.rsplit_once('.').map(|it| it.1.to_ascii_lowercase()).unwrap_or_default();
+	matches!(ext.as_str(), "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "bmp" | "ico")
+}
+
+/// Format byte count as human readable size
+fn format_size(size: u64) -> String {
+	const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+	if size < 1024 {
+		return format!("{size} B");
+	}
+	let mut size = size as f64 / 1024.0;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Describe binary change with file sizes
+pub(crate) fn binary_summary(diff: &DiffInfo, binary: &BinaryInfo) -> String {
+	let size = |size: Option<u64>| size.map(format_size).unwrap_or_else(|| "unknown size".to_string());
+	match diff.kind {
+		ChangeKind::Added => format!("Binary asset added ({})", size(binary.new_size)),
+		ChangeKind::Deleted => format!("Binary asset removed ({})", size(binary.old_size)),
+		_ => format!("Binary asset changed ({} → {})", size(binary.old_size), size(binary.new_size)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::{Config, StepConfig};
+	use crate::generator::{Step, TutorialGenerator};
+	use crate::git::fixture::TestRepo;
+	use crate::git::{GitBackend, GitRepository};
+
+	#[tokio::test]
+	async fn test_parts() {
+		let test = TestRepo::new("parts");
+		let first = test.commit("Add a");
+		test.git(&["tag", "wip", &first]);
+		test.commit("Extend a");
+		test.git(&["tag", "-a", "v1", "-m", "Basics\n\nLearn the basics."]);
+		test.git(&["branch", "part-1"]);
+		let skipped = test.commit("Wip");
+		test.commit("Add b");
+		test.git(&["branch", "part-2"]);
+		let skip = vec![StepConfig { commit: skipped, skip: true, ..StepConfig::default() }];
+		/// Title and intro of part started by a step
+		type PartOf<'a> = Option<(Option<&'a str>, Option<&'a str>)>;
+		fn parts(steps: &[Step]) -> Vec<(&str, PartOf<'_>)> {
+			steps.iter()
+				.map(|it| (it.title.as_str(), it.part.as_ref().map(|it| (it.title.as_deref(), it.intro.as_deref()))))
+				.collect()
+		}
+		for backend in [GitBackend::Process, GitBackend::Native] {
+			let repo = GitRepository::open(test.path(), backend).unwrap();
+			// part of skipped first step of a branch starts at next step
+			let config = Config { branches: vec!["part-1".to_string(), "part-2".to_string()], steps: skip.clone(), ..Config::default() };
+			let steps = TutorialGenerator::with_config(&repo, config).load(None).await.unwrap();
+			assert_eq!(parts(&steps), [
+				("Add a", Some((Some("part-1"), None))),
+				("Extend a", None),
+				("Add b", Some((Some("part-2"), None))),
+			], "{backend}");
+
+			// step with annotated tag ends a part, trailing steps form an untitled part,
+			// lightweight tag is ignored
+			let config = Config { range: Some("main".to_string()), steps: skip.clone(), ..Config::default() };
+			let steps = TutorialGenerator::with_config(&repo, config).load(None).await.unwrap();
+			assert_eq!(parts(&steps), [
+				("Add a", Some((Some("v1"), Some("Basics\n\nLearn the basics.")))),
+				("Extend a", None),
+				("Add b", Some((None, None))),
+			], "{backend}");
+		}
+	}
+}
@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::git::slider::compact;
+
+/// Algorithm used to compare lines of two files
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffAlgorithm {
+	/// Shortest edit script (Myers, 1986), default of git
+	#[default]
+	Myers,
+	/// Match lines which are unique in both files first
+	Patience,
+	/// Match least frequent lines first, fall back to myers
+	Histogram,
+}
+
+impl Display for DiffAlgorithm {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			DiffAlgorithm::Myers => "myers",
+			DiffAlgorithm::Patience => "patience",
+			DiffAlgorithm::Histogram => "histogram",
+		})
+	}
+}
+
+impl FromStr for DiffAlgorithm {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"myers" | "default" => Ok(DiffAlgorithm::Myers),
+			"patience" => Ok(DiffAlgorithm::Patience),
+			"histogram" => Ok(DiffAlgorithm::Histogram),
+			_ => Err(format!("Unknown diff algorithm `{s}`")),
+		}
+	}
+}
+
+/// How patches are produced
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DiffOptions {
+	pub algorithm: DiffAlgorithm,
+	/// Number of unchanged lines around each change
+	pub context: usize,
+}
+
+impl Default for DiffOptions {
+	fn default() -> Self {
+		Self {
+			algorithm: DiffAlgorithm::default(),
+			context: 3,
+		}
+	}
+}
+
+/// Single step of edit script, old and new line positions are implied by preceding steps
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Edit {
+	/// Line exist in both files
+	Equal,
+	/// Line only exist in old file
+	Delete,
+	/// Line only exist in new file
+	Insert,
+}
+
+/// Lines with more occurrences than this are never used as histogram anchor
+const MAX_CHAIN: usize = 64;
+
+/// Compute edit script turning `old` into `new`, ambiguous changes are slid like git's indent heuristic,
+/// deleted lines always come before inserted lines of the same change
+pub fn diff_lines<'a>(old_lines: &[&'a str], new_lines: &[&'a str], algorithm: DiffAlgorithm) -> Vec<Edit> {
+	// compare numbers instead of strings
+	let mut ids = HashMap::new();
+	let old = intern(&mut ids, old_lines);
+	let new = intern(&mut ids, new_lines);
+	let mut edits = Vec::with_capacity(old.len().max(new.len()));
+	let (o, n) = (0..old.len(), 0..new.len());
+	match algorithm {
+		DiffAlgorithm::Myers => myers(&old, &new, o, n, &mut edits),
+		DiffAlgorithm::Patience => patience(&old, &new, o, n, &mut edits),
+		DiffAlgorithm::Histogram => histogram(&old, &new, o, n, &mut edits),
+	}
+	group_changes(&mut edits);
+	compact(old_lines, new_lines, &edits)
+}
+
+fn intern<'a>(ids: &mut HashMap<&'a str, u32>, lines: &[&'a str]) -> Vec<u32> {
+	lines.iter()
+		.map(|it| {
+			let next = ids.len() as u32;
+			*ids.entry(*it).or_insert(next)
+		})
+		.collect()
+}
+
+/// Percentage of bytes kept from `old` in `new`, used to detect renames
+pub fn similarity(old: &str, new: &str) -> u8 {
+	let max = old.len().max(new.len());
+	if max == 0 {
+		return 100;
+	}
+	let old = old.split_inclusive('\n').collect::<Vec<_>>();
+	let new = new.split_inclusive('\n').collect::<Vec<_>>();
+	let mut line = 0;
+	let mut kept = 0;
+	for edit in diff_lines(&old, &new, DiffAlgorithm::Myers) {
+		if edit != Edit::Insert {
+			if edit == Edit::Equal {
+				kept += old[line].len();
+			}
+			line += 1;
+		}
+	}
+	(kept * 100 / max) as u8
+}
+
+/// Move deletions in front of insertions inside every run of changes
+fn group_changes(edits: &mut [Edit]) {
+	for run in edits.split_mut(|it| *it == Edit::Equal) {
+		run.sort_by_key(|it| *it != Edit::Delete);
+	}
+}
+
+/// Emit equal lines at both ends of ranges, return remaining ranges and number of common suffix lines
+fn trim(old: &[u32], new: &[u32], mut o: Range<usize>, mut n: Range<usize>, edits: &mut Vec<Edit>) -> (Range<usize>, Range<usize>, usize) {
+	while !o.is_empty() && !n.is_empty() && old[o.start] == new[n.start] {
+		o.start += 1;
+		n.start += 1;
+		edits.push(Edit::Equal);
+	}
+	let mut suffix = 0;
+	while !o.is_empty() && !n.is_empty() && old[o.end - 1] == new[n.end - 1] {
+		o.end -= 1;
+		n.end -= 1;
+		suffix += 1;
+	}
+	(o, n, suffix)
+}
+
+fn myers(old: &[u32], new: &[u32], o: Range<usize>, n: Range<usize>, edits: &mut Vec<Edit>) {
+	let (o, n, suffix) = trim(old, new, o, n, edits);
+	let (a, b) = (&old[o], &new[n]);
+	let (len_a, len_b) = (a.len() as isize, b.len() as isize);
+	let max = (len_a + len_b) as usize;
+	let offset = max as isize + 1;
+	let mut v = vec![0isize; 2 * max + 3];
+	// `trace[d]` hold diagonals `-d..=d` before step `d`
+	let mut trace: Vec<Vec<isize>> = Vec::new();
+	'search: for d in 0..=max as isize {
+		trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+		for k in (-d..=d).step_by(2) {
+			let at = |k: isize| v[(offset + k) as usize];
+			let mut x = if k == -d || (k != d && at(k - 1) < at(k + 1)) { at(k + 1) } else { at(k - 1) + 1 };
+			let mut y = x - k;
+			while x < len_a && y < len_b && a[x as usize] == b[y as usize] {
+				x += 1;
+				y += 1;
+			}
+			v[(offset + k) as usize] = x;
+			if x >= len_a && y >= len_b {
+				break 'search;
+			}
+		}
+	}
+
+	let mut script = Vec::with_capacity(max);
+	let (mut x, mut y) = (len_a, len_b);
+	for (d, v) in trace.iter().enumerate().skip(1).rev() {
+		let d = d as isize;
+		let at = |k: isize| v[(k + d) as usize];
+		let k = x - y;
+		let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+		let prev_x = at(prev_k);
+		let prev_y = prev_x - prev_k;
+		while x > prev_x && y > prev_y {
+			script.push(Edit::Equal);
+			x -= 1;
+			y -= 1;
+		}
+		script.push(if x == prev_x { Edit::Insert } else { Edit::Delete });
+		x = prev_x;
+		y = prev_y;
+	}
+	script.extend(std::iter::repeat_n(Edit::Equal, x as usize));
+	edits.extend(script.into_iter().rev());
+	edits.extend(std::iter::repeat_n(Edit::Equal, suffix));
+}
+
+fn patience(old: &[u32], new: &[u32], o: Range<usize>, n: Range<usize>, edits: &mut Vec<Edit>) {
+	let (o, n, suffix) = trim(old, new, o, n, edits);
+	// (count in old, position in old, count in new, position in new)
+	let mut lines: HashMap<u32, (usize, usize, usize, usize)> = HashMap::new();
+	for i in o.clone() {
+		let it = lines.entry(old[i]).or_default();
+		it.0 += 1;
+		it.1 = i;
+	}
+	for i in n.clone() {
+		if let Some(it) = lines.get_mut(&new[i]) {
+			it.2 += 1;
+			it.3 = i;
+		}
+	}
+	let mut unique = lines.into_values()
+		.filter(|it| it.0 == 1 && it.2 == 1)
+		.map(|it| (it.1, it.3))
+		.collect::<Vec<_>>();
+	if unique.is_empty() {
+		myers(old, new, o, n, edits);
+	} else {
+		unique.sort_by_key(|it| it.1);
+		let (mut prev_o, mut prev_n) = (o.start, n.start);
+		for (at_o, at_n) in longest_increasing(&unique) {
+			patience(old, new, prev_o..at_o, prev_n..at_n, edits);
+			edits.push(Edit::Equal);
+			(prev_o, prev_n) = (at_o + 1, at_n + 1);
+		}
+		patience(old, new, prev_o..o.end, prev_n..n.end, edits);
+	}
+	edits.extend(std::iter::repeat_n(Edit::Equal, suffix));
+}
+
+/// Longest subsequence of `pairs` (sorted by new position) with increasing old position
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+	// index of last pair of best subsequence with length `i + 1`
+	let mut tails: Vec<usize> = Vec::new();
+	let mut prev = vec![usize::MAX; pairs.len()];
+	for (i, pair) in pairs.iter().enumerate() {
+		let pos = tails.partition_point(|it| pairs[*it].0 < pair.0);
+		if pos > 0 {
+			prev[i] = tails[pos - 1];
+		}
+		if pos == tails.len() {
+			tails.push(i);
+		} else {
+			tails[pos] = i;
+		}
+	}
+	let mut result = Vec::with_capacity(tails.len());
+	let mut at = tails.last().copied().unwrap_or(usize::MAX);
+	while at != usize::MAX {
+		result.push(pairs[at]);
+		at = prev[at];
+	}
+	result.reverse();
+	result
+}
+
+fn histogram(old: &[u32], new: &[u32], o: Range<usize>, n: Range<usize>, edits: &mut Vec<Edit>) {
+	let (o, n, suffix) = trim(old, new, o, n, edits);
+	let mut positions: HashMap<u32, Vec<usize>> = HashMap::new();
+	for i in o.clone() {
+		positions.entry(old[i]).or_default().push(i);
+	}
+	// (occurrences, length, old start, new start) of best common region
+	let mut best: Option<(usize, usize, usize, usize)> = None;
+	let mut i = n.start;
+	while i < n.end {
+		let mut next = i + 1;
+		if let Some(found) = positions.get(&new[i]).filter(|it| it.len() <= MAX_CHAIN) {
+			for &at in found {
+				let (mut start_o, mut start_n) = (at, i);
+				while start_o > o.start && start_n > n.start && old[start_o - 1] == new[start_n - 1] {
+					start_o -= 1;
+					start_n -= 1;
+				}
+				let mut len = i - start_n + 1;
+				while start_o + len < o.end && start_n + len < n.end && old[start_o + len] == new[start_n + len] {
+					len += 1;
+				}
+				let count = found.len();
+				if best.is_none_or(|(c, l, _, _)| count < c || (count == c && len > l)) {
+					best = Some((count, len, start_o, start_n));
+					next = next.max(start_n + len);
+				}
+			}
+		}
+		i = next;
+	}
+	match best {
+		None => myers(old, new, o, n, edits),
+		Some((_, len, start_o, start_n)) => {
+			histogram(old, new, o.start..start_o, n.start..start_n, edits);
+			edits.extend(std::iter::repeat_n(Edit::Equal, len));
+			histogram(old, new, start_o + len..o.end, start_n + len..n.end, edits);
+		}
+	}
+	edits.extend(std::iter::repeat_n(Edit::Equal, suffix));
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::git::algorithm::{diff_lines, DiffAlgorithm, Edit, similarity};
+
+	/// Render edit script as `=`, `-` and `+`
+	fn script(old: &str, new: &str, algorithm: DiffAlgorithm) -> String {
+		let old = old.split_inclusive('\n').collect::<Vec<_>>();
+		let new = new.split_inclusive('\n').collect::<Vec<_>>();
+		diff_lines(&old, &new, algorithm).iter()
+			.map(|it| match it {
+				Edit::Equal => '=',
+				Edit::Delete => '-',
+				Edit::Insert => '+',
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_diff_lines() {
+		let old = "a\nb\nc\na\nb\nb\na\n";
+		let new = "c\nb\na\nb\na\nc\n";
+		for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Patience, DiffAlgorithm::Histogram] {
+			let edits = script(old, new, algorithm);
+			assert_eq!(edits.chars().filter(|it| *it != '+').count(), 7, "{algorithm}");
+			assert_eq!(edits.chars().filter(|it| *it != '-').count(), 6, "{algorithm}");
+		}
+		assert_eq!(script(old, new, DiffAlgorithm::Myers).matches('=').count(), 4);
+
+		assert_eq!(script("", "a\nb\n", DiffAlgorithm::Myers), "++");
+		assert_eq!(script("a\nb\n", "", DiffAlgorithm::Histogram), "--");
+		assert_eq!(script("a\nb\nc\n", "a\nx\nc\n", DiffAlgorithm::Patience), "=-+=");
+		// missing newline make last line differ
+		assert_eq!(script("a\nb", "a\nb\n", DiffAlgorithm::Myers), "=-+");
+	}
+
+	#[test]
+	fn test_patience_anchor() {
+		// blank line is the only unique line in both files which keep its position
+		let old = "fn a() {\n}\n\nfn b() {\n}\n";
+		let new = "fn b() {\n}\n\nfn c() {\n}\n";
+		assert_eq!(script(old, new, DiffAlgorithm::Patience), "-+==-+=");
+	}
+
+	#[test]
+	fn test_similarity() {
+		assert_eq!(similarity("a\nb\n", "a\nb\n"), 100);
+		assert_eq!(similarity("a\nb\n", "a\nc\n"), 50);
+		assert_eq!(similarity("", "a\n"), 0);
+	}
+}
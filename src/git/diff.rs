@@ -1,113 +1,188 @@
-use std::fmt::{Debug, Display};
+use std::fmt::{Debug, Display, Formatter};
 use std::future::Future;
 use std::io::BufRead;
-use std::ops::{Deref, Range};
+use std::ops::Range;
 use std::pin::Pin;
 
-use tokio::io;
-use tokio::io::BufReader;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use tokio::io::{AsyncRead, BufReader};
 use tokio::process::{Child, ChildStdout};
 use tracing::warn;
 
+use crate::git::algorithm::{diff_lines, DiffOptions, Edit};
+use crate::git::slider::compact;
+use crate::git::word_diff::WordDiff;
+use crate::error::{ApplyError, ParseError, ParseErrorKind, Result, StepdocsError};
 use crate::read_or_none;
 use crate::util::iter::AsyncIterator;
 use crate::util::peekable_reader::PeekableLine;
-use crate::util::string::StringExt;
+use crate::util::proc::check_exit;
 
-pub struct GitDiffParser {
-	_child: Child,
-	inner: PeekableLine<ChildStdout>,
+pub struct GitDiffParser<R = ChildStdout> {
+	child: Option<Child>,
+	/// commit being parsed, used as error context
+	commit: Option<String>,
+	inner: PeekableLine<R>,
 }
 
 impl GitDiffParser {
-	pub fn new(_child: Child, stdout: ChildStdout) -> Self {
+	pub fn new(child: Child, stdout: ChildStdout) -> Self {
 		Self {
-			_child,
+			child: Some(child),
+			commit: None,
 			inner: PeekableLine::new(BufReader::new(stdout)),
 		}
 	}
+}
+
+impl<R: AsyncRead + Unpin> GitDiffParser<R> {
+	/// Parse diff from any reader which produce `git diff` compatible output
+	pub fn from_reader(reader: R) -> Self {
+		Self {
+			child: None,
+			commit: None,
+			inner: PeekableLine::new(BufReader::new(reader)),
+		}
+	}
+
+	/// Attach commit hash to reported errors
+	pub fn with_commit(mut self, commit: impl Into<String>) -> Self {
+		self.commit = Some(commit.into());
+		self
+	}
+
+	/// End of stream, report failure if `git` was failed
+	async fn finish<T>(&mut self) -> Result<Option<T>> {
+		if let Some(child) = &mut self.child {
+			let command = format!("show {}", self.commit.as_deref().unwrap_or_default());
+			check_exit(child, command).await?;
+		}
+		Ok(None)
+	}
 
 	/// Get next patch set from git 
-	pub async fn next_diff(&mut self) -> io::Result<Option<DiffInfo>> {
+	pub async fn next_diff(&mut self) -> Result<Option<DiffInfo>> {
 		let mut command = String::with_capacity(64);
-		read_or_none!(self, command);
-		let mut new_file = Option::<String>::None;
-		let mut index = String::with_capacity(32);
-		read_or_none!(self, index);
-		if index.starts_with("new") {
-			new_file.replace(index);
-			index = String::with_capacity(32);
-			read_or_none!(self, index);
+		while command.is_empty() {
+			read_or_none!(self, command);
+		}
+		if !command.starts_with("diff --git ") {
+			return Err(ParseError::new(ParseErrorKind::DiffHeader, command)
+				.at_line(self.inner.line())
+				.in_commit(self.commit.as_deref())
+				.into());
 		}
-		let mut buf = String::new();
+		let mut info = DiffInfo::new(command);
+		let mut has_rename = false;
 
-		read_or_none!(self, buf);
-		let source = if buf.starts_with("---") {
-			buf[4..].to_string()
-		} else {
-			warn!("Diff: Wrong target marker expect `---` found `{}`", buf);
-			return Ok(None);
-		};
-		buf.clear();
-		read_or_none!(self, buf);
-		let target = if buf.starts_with("+++") {
-			buf[4..].to_string()
-		} else {
-			warn!("Diff: Wrong target marker expect `+++` found `{}`", buf);
-			return Ok(None);
-		};
+		// extended header lines
+		loop {
+			let peek = self.inner.peek_line().await?;
+			let peek = peek.trim_end_matches(['\n', '\r']);
+			if peek.is_empty() || peek.starts_with("diff ") || peek.starts_with("@@") {
+				break;
+			}
+			if let Some(mode) = peek.strip_prefix("old mode ") {
+				info.old_mode = parse_mode(mode);
+			} else if let Some(mode) = peek.strip_prefix("new mode ") {
+				info.new_mode = parse_mode(mode);
+				if info.kind == ChangeKind::Modified {
+					info.kind = ChangeKind::ModeChange;
+				}
+			} else if let Some(mode) = peek.strip_prefix("deleted file mode ") {
+				info.kind = ChangeKind::Deleted;
+				info.old_mode = parse_mode(mode);
+				info.new_mode = None;
+				info.new_path = None;
+			} else if let Some(mode) = peek.strip_prefix("new file mode ") {
+				info.kind = ChangeKind::Added;
+				info.old_mode = None;
+				info.new_mode = parse_mode(mode);
+				info.old_path = None;
+			} else if let Some(path) = peek.strip_prefix("rename from ") {
+				info.kind = ChangeKind::Renamed;
+				info.old_path = Some(unquote(path));
+				has_rename = true;
+			} else if let Some(path) = peek.strip_prefix("rename to ") {
+				info.new_path = Some(unquote(path));
+			} else if let Some(path) = peek.strip_prefix("copy from ") {
+				info.kind = ChangeKind::Copied;
+				info.old_path = Some(unquote(path));
+				has_rename = true;
+			} else if let Some(path) = peek.strip_prefix("copy to ") {
+				info.new_path = Some(unquote(path));
+			} else if let Some(score) = peek.strip_prefix("similarity index ") {
+				info.similarity = score.trim_end_matches('%').parse().ok();
+			} else if let Some(score) = peek.strip_prefix("dissimilarity index ") {
+				info.similarity = score.trim_end_matches('%').parse::<u8>().ok().map(|it| 100 - it.min(100));
+			} else if let Some(index) = peek.strip_prefix("index ") {
+				let (index, mode) = index.split_once(' ').unwrap_or((index, ""));
+				info.index = Some(index.to_string());
+				if let Some(mode) = parse_mode(mode) {
+					info.old_mode = Some(mode);
+					info.new_mode = Some(mode);
+				}
+			} else if peek.starts_with("Binary files ") {
+				self.inner.consume_peek();
+				info.binary = Some(BinaryInfo::default());
+				break;
+			} else if peek == "GIT binary patch" {
+				self.inner.consume_peek();
+				info.binary = Some(self.read_binary_patch().await?);
+				break;
+			} else if let Some(path) = peek.strip_prefix("--- ") {
+				if !has_rename {
+					info.old_path = strip_path(path, "a/");
+				}
+			} else if let Some(path) = peek.strip_prefix("+++ ") {
+				if !has_rename {
+					info.new_path = strip_path(path, "b/");
+				}
+				self.inner.consume_peek();
+				break;
+			} else {
+				warn!("Diff: Unknown header line `{}`", peek);
+			}
+			self.inner.consume_peek();
+		}
+		if let Some(binary) = &mut info.binary {
+			if let Some((old, new)) = info.index.as_deref().and_then(|it| it.split_once("..")) {
+				binary.old_id = Some(old.to_string()).filter(|it| !is_null_id(it));
+				binary.new_id = Some(new.to_string()).filter(|it| !is_null_id(it));
+			}
+			return Ok(Some(info));
+		}
 
-		buf.clear();
 		// miminum diff = 7 lines * 32 chars[max=120]
 		let mut diff_str = String::with_capacity(8 * 32);
 		let mut diff_idx: Vec<(DiffOffset, Vec<PatchIndex>)> = Vec::new();
 		let mut patch_idx: Vec<PatchIndex> = Vec::with_capacity(16);
 
 		let inner = &mut self.inner;
+		let error = |kind: ParseErrorKind, text: &str, line: usize| -> StepdocsError {
+			ParseError::new(kind, text)
+				.at_line(line)
+				.in_commit(self.commit.as_deref())
+				.in_file(Some(info.path()))
+				.into()
+		};
 		let mut diff_offset: DiffOffset = DiffOffset::default();
 		loop {
+			let line_no = inner.line() + 1;
 			let _peek = inner.peek_line().await?;
 			let peek = _peek.trim_end_matches(['\n']);
 			// end of diff for this file
 			if peek.is_empty() || peek.starts_with("diff") { break; }
 			if peek.starts_with("@@") {
-				if peek.ends_with("@@") {
-					if !diff_offset.is_zero() {
-						diff_idx.push((diff_offset, patch_idx));
-						patch_idx = Vec::with_capacity(16);
-					}
-					diff_offset = match DiffOffset::parse(peek) {
-						Some(it) => { it }
-						None => {
-							warn!("Illegal offset info {:?}",peek);
-							return Ok(None);
-						}
-					};
-					inner.consume_peek();
-					continue;
-				} else {
-					let end_cursor = peek.drop(2).find("@@");
-					if let Some(end) = end_cursor {
-						if !diff_offset.is_zero() {
-							diff_idx.push((diff_offset, patch_idx));
-							patch_idx = Vec::with_capacity(16);
-						}
-						let marker = &peek[..end + 4];
-						diff_offset = match DiffOffset::parse(marker) {
-							Some(val) => { val }
-							None => {
-								warn!("Illegal offset info {:?}",peek);
-								return Ok(None);
-							}
-						};
-						diff_str.push_str(&peek[end + 4..]);
-						inner.consume_peek();
-						continue;
-					} else {
-						warn!("Illegal token {:?}",peek);
-						return Ok(None);
-					}
+				if !diff_offset.is_zero() {
+					diff_idx.push((diff_offset, patch_idx));
+					patch_idx = Vec::with_capacity(16);
 				}
+				diff_offset = DiffOffset::parse(peek)
+					.map_err(|err| error(err.kind, &err.text, line_no))?;
+				inner.consume_peek();
+				continue;
 			}
 
 			let start = diff_str.len();
@@ -117,54 +192,292 @@ impl GitDiffParser {
 					Some(' ') => DiffType::None,
 					Some('+') => DiffType::Add,
 					Some('-') => DiffType::Remove,
+					Some('\\') => DiffType::NoNewline,
 					_ => {
-						warn!("Invalid patch {:?}",_peek);
-						return Ok(None);
+						return Err(error(ParseErrorKind::HunkLine, peek, line_no));
 					}
 				},
 				start,
-				end: diff_str.len() - 1,
+				end: start + _peek.trim_end_matches('\n').len(),
 			});
 			inner.consume_peek();
 		}
-		diff_idx.push((diff_offset, patch_idx));
-		Ok(
-			Some(DiffInfo {
-				command,
-				source,
-				target,
-				new_file,
-				index,
-				diffs: Patch::new_with_index(diff_str, diff_idx),
-			})
-		)
+		// pure rename or mode change has no hunk
+		if !diff_offset.is_zero() {
+			diff_idx.push((diff_offset, patch_idx));
+		}
+		// git stopped in the middle of hunk
+		if let Some((offset, _)) = diff_idx.iter().find(|(offset, idx)| !offset.matches(idx)) {
+			return Err(error(ParseErrorKind::HunkLength, &offset.to_string(), inner.line()));
+		}
+		if info.kind == ChangeKind::ModeChange && (info.old_mode == info.new_mode || !diff_idx.is_empty()) {
+			info.kind = ChangeKind::Modified;
+		}
+		info.diffs = Patch::new_with_index(diff_str, diff_idx);
+		Ok(Some(info))
 	}
 }
 
-impl AsyncIterator<io::Error> for GitDiffParser {
+impl<R: AsyncRead + Unpin> GitDiffParser<R> {
+	/// Read `literal`/`delta` blocks after `GIT binary patch`,
+	/// first block produce new content and second one (reverse patch) produce old content
+	async fn read_binary_patch(&mut self) -> Result<BinaryInfo> {
+		let mut info = BinaryInfo::default();
+		let mut block = 0;
+		loop {
+			let peek = self.inner.peek_line().await?;
+			let peek = peek.trim_end_matches(['\n', '\r']);
+			let size = if let Some(size) = peek.strip_prefix("literal ") {
+				size.parse::<u64>().ok()
+			} else if peek.starts_with("delta ") {
+				// size of delta is not size of the blob
+				None
+			} else {
+				break;
+			};
+			match block {
+				0 => info.new_size = size,
+				_ => info.old_size = size,
+			}
+			block += 1;
+			self.inner.consume_peek();
+			// base85 encoded data until blank line
+			loop {
+				let peek = self.inner.peek_line().await?;
+				if peek.is_empty() {
+					break;
+				}
+				let blank = peek.trim().is_empty();
+				self.inner.consume_peek();
+				if blank {
+					break;
+				}
+			}
+		}
+		Ok(info)
+	}
+}
+
+impl<R: AsyncRead + Unpin> AsyncIterator<StepdocsError> for GitDiffParser<R> {
 	type Item = DiffInfo;
 
-	fn next<'a>(&'a mut self) -> Pin<Box<dyn Future<Output=Result<Option<Self::Item>, io::Error>> + 'a>> {
+	fn next<'a>(&'a mut self) -> Pin<Box<dyn Future<Output=Result<Option<Self::Item>>> + 'a>> {
 		Box::pin(self.next_diff())
 	}
 }
 
-#[derive(Debug)]
+/// Kind of change made to a file
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+	Added,
+	Deleted,
+	Modified,
+	Renamed,
+	Copied,
+	/// Only file mode was changed
+	ModeChange,
+}
+
+impl Display for ChangeKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			ChangeKind::Added => "added",
+			ChangeKind::Deleted => "deleted",
+			ChangeKind::Modified => "modified",
+			ChangeKind::Renamed => "renamed",
+			ChangeKind::Copied => "copied",
+			ChangeKind::ModeChange => "mode changed",
+		})
+	}
+}
+
+#[derive(Debug, Serialize)]
 pub struct DiffInfo {
+	/// `diff --git` header line
+	#[serde(skip)]
 	pub command: String,
-	pub source: String,
-	pub target: String,
-	pub new_file: Option<String>,
-	pub index: String,
+	pub kind: ChangeKind,
+	/// Path before change, `None` if file was added
+	pub old_path: Option<String>,
+	/// Path after change, `None` if file was deleted
+	pub new_path: Option<String>,
+	pub old_mode: Option<u32>,
+	pub new_mode: Option<u32>,
+	/// Similarity percentage of renamed or copied file
+	pub similarity: Option<u8>,
+	/// Abbreviated blob ids as `old..new`
+	pub index: Option<String>,
+	/// Set if git consider this file as binary, `diffs` will be empty
+	pub binary: Option<BinaryInfo>,
+	#[serde(rename = "hunks")]
 	pub diffs: Patch,
 }
 
-#[derive(Debug, Default)]
+/// Information of binary file change, blob ids are abbreviated unless diff is created with `--full-index`
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BinaryInfo {
+	pub old_id: Option<String>,
+	pub new_id: Option<String>,
+	/// Size in bytes, only available from `GIT binary patch` with literal content
+	pub old_size: Option<u64>,
+	pub new_size: Option<u64>,
+}
+
+impl DiffInfo {
+	pub(crate) fn new(command: String) -> Self {
+		let (old_path, new_path) = parse_command_paths(&command);
+		Self {
+			command,
+			kind: ChangeKind::Modified,
+			old_path,
+			new_path,
+			old_mode: None,
+			new_mode: None,
+			similarity: None,
+			index: None,
+			binary: None,
+			diffs: Patch::new_with_index(String::new(), Vec::new()),
+		}
+	}
+
+	/// Path of changed file relative to repository root,
+	/// use old path if file was deleted
+	pub fn path(&self) -> &str {
+		self.new_path.as_deref()
+			.or(self.old_path.as_deref())
+			.unwrap_or_default()
+	}
+
+	pub fn is_binary(&self) -> bool {
+		self.binary.is_some()
+	}
+
+	/// Old and new blob id from `index` line, `None` for missing side
+	pub fn blob_ids(&self) -> (Option<&str>, Option<&str>) {
+		let Some((old, new)) = self.index.as_deref().and_then(|it| it.split_once("..")) else {
+			return (None, None);
+		};
+		let old = Some(old).filter(|it| !it.is_empty() && !is_null_id(it));
+		let new = Some(new).filter(|it| !it.is_empty() && !is_null_id(it));
+		(old, new)
+	}
+
+	/// `true` if file was moved or copied from another path
+	pub fn is_rename(&self) -> bool {
+		matches!(self.kind, ChangeKind::Renamed | ChangeKind::Copied)
+	}
+}
+
+fn is_null_id(id: &str) -> bool {
+	id.bytes().all(|it| it == b'0')
+}
+
+fn parse_mode(mode: &str) -> Option<u32> {
+	u32::from_str_radix(mode.trim(), 8).ok()
+}
+
+/// Remove C-style quotes git adds to path with special or non-ASCII characters,
+/// octal escapes are bytes of UTF-8 path
+fn unquote(path: &str) -> String {
+	let path = path.trim_end_matches('\t');
+	let Some(quoted) = path.strip_prefix('"').and_then(|it| it.strip_suffix('"')) else {
+		return path.to_string();
+	};
+	let mut bytes = Vec::with_capacity(quoted.len());
+	let mut rest = quoted.as_bytes();
+	while let Some((&byte, tail)) = rest.split_first() {
+		rest = tail;
+		if byte != b'\\' {
+			bytes.push(byte);
+			continue;
+		}
+		let Some((&escape, tail)) = rest.split_first() else {
+			bytes.push(byte);
+			break;
+		};
+		rest = tail;
+		bytes.push(match escape {
+			b'a' => 0x07,
+			b'b' => 0x08,
+			b't' => b'\t',
+			b'n' => b'\n',
+			b'v' => 0x0b,
+			b'f' => 0x0c,
+			b'r' => b'\r',
+			b'0'..=b'7' => {
+				// up to 3 octal digits
+				let digits = 1 + rest.iter().take(2).take_while(|it| matches!(it, b'0'..=b'7')).count();
+				let octal = std::iter::once(escape).chain(rest[..digits - 1].iter().copied())
+					.fold(0u32, |value, it| value * 8 + (it - b'0') as u32);
+				rest = &rest[digits - 1..];
+				octal as u8
+			}
+			other => other,
+		});
+	}
+	String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Split first path from paths of `diff --git`, quoted path ends at its closing quote
+fn next_path(paths: &str) -> (String, &str) {
+	if paths.starts_with('"') {
+		let mut escaped = false;
+		for (at, ch) in paths.char_indices().skip(1) {
+			match ch {
+				'\\' if !escaped => escaped = true,
+				'"' if !escaped => return (unquote(&paths[..=at]), &paths[at + 1..]),
+				_ => escaped = false,
+			}
+		}
+	}
+	match paths.find(" \"b/") {
+		Some(at) => (paths[..at].to_string(), &paths[at..]),
+		None => (paths.to_string(), ""),
+	}
+}
+
+/// Parse path from `---`/`+++` line, `/dev/null` become `None`
+fn strip_path(path: &str, prefix: &str) -> Option<String> {
+	let path = unquote(path);
+	if path == "/dev/null" {
+		return None;
+	}
+	Some(path.strip_prefix(prefix).map(str::to_string).unwrap_or(path))
+}
+
+/// Guess old and new path from `diff --git a/<old> b/<new>`
+fn parse_command_paths(command: &str) -> (Option<String>, Option<String>) {
+	let paths = command.trim_start_matches("diff --git ");
+	// quoted paths are never ambiguous
+	if paths.starts_with('"') || paths.contains(" \"b/") {
+		let (old, rest) = next_path(paths);
+		let (new, _) = next_path(rest.trim_start());
+		return (old.strip_prefix("a/").map(str::to_string), new.strip_prefix("b/").map(str::to_string));
+	}
+	// both paths are equal for anything but rename and copy
+	if paths.len() > 5 && (paths.len() - 5).is_multiple_of(2) {
+		let len = (paths.len() - 5) / 2;
+		if let (Some(old), Some(new)) = (paths.get(2..2 + len), paths.get(len + 5..)) {
+			if old == new && paths.starts_with("a/") && paths[2 + len..].starts_with(" b/") {
+				return (Some(old.to_string()), Some(new.to_string()));
+			}
+		}
+	}
+	match paths.split_once(" b/") {
+		Some((old, new)) => (strip_path(old, "a/"), Some(unquote(new))),
+		None => (None, None),
+	}
+}
+
+#[derive(Debug, Default, Serialize)]
 pub struct DiffOffset {
 	pub source_start: u64,
 	pub source_lines: u64,
 	pub target_start: u64,
 	pub target_lines: u64,
+	/// Function context after closing `@@`, such as `fn main() {`
+	pub section_heading: Option<String>,
 }
 
 impl DiffOffset {
@@ -176,40 +489,68 @@ impl DiffOffset {
 	}
 }
 
-impl DiffOffset {
-	pub fn parse(token: &str) -> Option<Self> {
-		let offset: DiffOffset;
-		let mut offs = token.splitn(4, ' ');
-		let _ = offs.next();// @@
-		let src = offs.next();
-		let tar = offs.next();
-
-		if let (Some(source), Some(target)) = (src, tar) {
-			let (source_start, source_lines) = Self::parse_section(source)?;
-			let (target_start, target_lines) = Self::parse_section(target)?;
-
-			offset = DiffOffset {
-				source_start,
-				source_lines,
-				target_start,
-				target_lines,
-			}
+impl Display for DiffOffset {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		// line count is omitted when it is 1, same as git
+		let range = |f: &mut Formatter<'_>, start: u64, lines: u64| if lines == 1 {
+			write!(f, "{start}")
 		} else {
-			warn!("Diff: Wrong offset message `{}`", token);
-			return None;
+			write!(f, "{start},{lines}")
+		};
+		f.write_str("@@ -")?;
+		range(f, self.source_start, self.source_lines)?;
+		f.write_str(" +")?;
+		range(f, self.target_start, self.target_lines)?;
+		f.write_str(" @@")?;
+		if let Some(heading) = &self.section_heading {
+			write!(f, " {heading}")?;
 		}
-		Some(offset)
+		Ok(())
+	}
+}
+
+impl DiffOffset {
+	/// Parse `@@ -start[,lines] +start[,lines] @@[ section heading]`
+	pub fn parse(token: &str) -> Result<Self, ParseError> {
+		let error = || ParseError::new(ParseErrorKind::HunkHeader, token);
+		let rest = token.strip_prefix("@@ ").ok_or_else(error)?;
+		let (ranges, heading) = match rest.find(" @@") {
+			Some(end) => (&rest[..end], &rest[end + 3..]),
+			None => return Err(error()),
+		};
+		let (source, target) = ranges.split_once(' ').ok_or_else(error)?;
+		let source = source.strip_prefix('-').ok_or_else(error)?;
+		let target = target.strip_prefix('+').ok_or_else(error)?;
+		let (source_start, source_lines) = Self::parse_section(source).ok_or_else(error)?;
+		let (target_start, target_lines) = Self::parse_section(target).ok_or_else(error)?;
+		let heading = heading.strip_prefix(' ').unwrap_or(heading).trim_end();
+
+		Ok(DiffOffset {
+			source_start,
+			source_lines,
+			target_start,
+			target_lines,
+			section_heading: Some(heading.to_string()).filter(|it| !it.is_empty()),
+		})
+	}
+
+	/// Check if number of lines in hunk is equal to number declared in this header
+	fn matches(&self, index: &[PatchIndex]) -> bool {
+		let (source, target) = index.iter().fold((0u64, 0u64), |(source, target), it| match it.typ {
+			DiffType::Add => (source, target + 1),
+			DiffType::Remove => (source + 1, target),
+			DiffType::None => (source + 1, target + 1),
+			DiffType::NoNewline => (source, target),
+		});
+		source == self.source_lines && target == self.target_lines
 	}
 
 	fn parse_section(token: &str) -> Option<(u64, u64)> {
-		let (line, lines) = token.split_at(token.find(',')?);
-		let lines = &lines[1..];
-		Some(
-			(
-				line.trim_start_matches(['-', '+']).parse::<u64>().ok()?,
-				lines.trim_start_matches(['-', '+']).parse::<u64>().ok()?
-			)
-		)
+		match token.split_once(',') {
+			Some((line, lines)) => Some((line.parse().ok()?, lines.parse().ok()?)),
+			// `-5` is `-5,1`
+			None => Some((token.parse().ok()?, 1)),
+		}
 	}
 }
 
@@ -219,11 +560,14 @@ pub struct Patch {
 	index: Vec<(DiffOffset, Vec<PatchIndex>)>,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum DiffType {
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffType {
 	Add,
 	Remove,
 	None,
+	/// `\ No newline at end of file` marker, apply to previous line
+	NoNewline,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -240,74 +584,44 @@ impl From<&PatchIndex> for Range<usize> {
 }
 
 impl Patch {
-	pub fn parse(diff: String) -> Result<Self, String> {
+	pub fn parse(diff: String) -> Result<Self, ParseError> {
 		let mut index = Vec::with_capacity(16);
-		let mut reader = std::io::BufReader::new(diff.as_bytes()).lines().peekable();
+		let mut reader = std::io::BufReader::new(diff.as_bytes()).lines().enumerate().peekable();
 		let mut patch_idx: Vec<PatchIndex> = Vec::with_capacity(16);
 		let mut off = 0;
 		let mut diff_offset: DiffOffset = DiffOffset::default();
-		while let Some(line) = reader.peek() {
+		while let Some((line_no, line)) = reader.peek() {
+			let line_no = *line_no + 1;
 			let _peek = line.as_ref().unwrap();
 			let peek = _peek.trim_end_matches(['\n']);
 			// end of diff for this file
 			if peek.is_empty() || peek.starts_with("diff") { break; }
 			if peek.starts_with("@@") {
-				if peek.ends_with("@@") {
-					if !diff_offset.is_zero() {
-						index.push((diff_offset, patch_idx));
-						patch_idx = Vec::with_capacity(16);
-					}
-					diff_offset = match DiffOffset::parse(peek) {
-						Some(it) => {
-							off += _peek.len() + 1;
-							it
-						}
-						None => {
-							warn!("Illegal offset info {:?}",peek);
-							return Err(diff);
-						}
-					};
-					reader.next();
-					continue;
-				} else {
-					let end_cursor = peek.drop(2).find("@@");
-					if let Some(end) = end_cursor {
-						if !diff_offset.is_zero() {
-							index.push((diff_offset, patch_idx));
-							patch_idx = Vec::with_capacity(16);
-						}
-						let marker = &peek[..end + 4];
-						diff_offset = match DiffOffset::parse(marker) {
-							Some(val) => { val }
-							None => {
-								warn!("Illegal offset info {:?}",peek);
-								return Err(diff);
-							}
-						};
-						off += peek.len() - (end + 4);
-						reader.next();
-						continue;
-					} else {
-						warn!("Illegal token {:?}",peek);
-						return Err(diff);
-					}
+				if !diff_offset.is_zero() {
+					index.push((diff_offset, patch_idx));
+					patch_idx = Vec::with_capacity(16);
 				}
+				diff_offset = DiffOffset::parse(peek).map_err(|err| err.at_line(line_no))?;
+				off += _peek.len() + 1;
+				reader.next();
+				continue;
 			}
 
 			let start = off;
+			let end = off + _peek.len();
 			off += _peek.len() + 1;
 			patch_idx.push(PatchIndex {
 				typ: match _peek.chars().next() {
 					Some(' ') => DiffType::None,
 					Some('+') => DiffType::Add,
 					Some('-') => DiffType::Remove,
+					Some('\\') => DiffType::NoNewline,
 					_ => {
-						warn!("Invalid patch {:?}",_peek);
-						return Err(diff);
+						return Err(ParseError::new(ParseErrorKind::HunkLine, peek).at_line(line_no));
 					}
 				},
 				start,
-				end: off - 1,
+				end,
 			});
 			reader.next();
 		}
@@ -319,11 +633,67 @@ impl Patch {
 		Self { raw_diff: diff, index }
 	}
 
+	/// Compare `old` and `new` line by line and build patch with `options.context` lines around each change
+	pub fn diff(old: &str, new: &str, options: &DiffOptions) -> Self {
+		let old = old.split_inclusive('\n').collect::<Vec<_>>();
+		let new = new.split_inclusive('\n').collect::<Vec<_>>();
+		let edits = diff_lines(&old, &new, options.algorithm);
+		// line positions in old and new file before each edit
+		let mut positions = Vec::with_capacity(edits.len() + 1);
+		let (mut o, mut n) = (0, 0);
+		for edit in &edits {
+			positions.push((o, n));
+			match edit {
+				Edit::Equal => (o, n) = (o + 1, n + 1),
+				Edit::Delete => o += 1,
+				Edit::Insert => n += 1,
+			}
+		}
+		positions.push((o, n));
+
+		let is_change = |at: &usize| edits[*at] != Edit::Equal;
+		let context = options.context;
+		let mut raw_diff = String::new();
+		let mut index = Vec::new();
+		let mut next = 0;
+		while let Some(first) = (next..edits.len()).find(is_change) {
+			// join changes separated by less than two contexts
+			let mut last = first;
+			while let Some(change) = (last + 1..edits.len()).find(is_change) {
+				if change - last - 1 > context * 2 {
+					break;
+				}
+				last = change;
+			}
+			let start = first.saturating_sub(context).max(next);
+			let end = (last + 1 + context).min(edits.len());
+			next = end;
+
+			let ((source, target), (source_end, target_end)) = (positions[start], positions[end]);
+			let (source_lines, target_lines) = ((source_end - source) as u64, (target_end - target) as u64);
+			let offset = DiffOffset {
+				// empty range point to line before it
+				source_start: source as u64 + (source_lines > 0) as u64,
+				source_lines,
+				target_start: target as u64 + (target_lines > 0) as u64,
+				target_lines,
+				section_heading: section_heading(&old[..source]),
+			};
+			let mut patch = Vec::with_capacity(end - start);
+			let (mut o, mut n) = positions[start];
+			for edit in &edits[start..end] {
+				push_line(&mut raw_diff, &mut patch, *edit, &old, &new, &mut o, &mut n);
+			}
+			index.push((offset, patch));
+		}
+		Self { raw_diff, index }
+	}
+
 	pub fn patches(&self) -> usize {
 		self.index.len()
 	}
 
-	pub fn get_patch(&self, index: usize) -> Option<PatchInfo> {
+	pub fn get_patch(&self, index: usize) -> Option<PatchInfo<'_>> {
 		let (offset, index) = self.index.get(index)?;
 		let content_ptr = index.first().map(|it| it.start).unwrap_or_default();
 		let content_end = index.last().map(|it| it.end).unwrap_or_default();
@@ -331,7 +701,8 @@ impl Patch {
 			offset,
 			index,
 			content_ptr,
-			contents: &self.raw_diff[content_ptr..=content_end],
+			// last line may not end with newline
+			contents: &self.raw_diff[content_ptr..(content_end + 1).min(self.raw_diff.len())],
 		})
 	}
 
@@ -341,39 +712,233 @@ impl Patch {
 		Some(&self.raw_diff[offset])
 	}
 
-	pub fn normalize_patch(&mut self, patch: usize) {
-		let mut swap_idx = (0, 0);
-		let mut swap_line = (0, 0);
-		{
-			let (_, index) = if let Some(it) = self.index.get(patch) { it } else {
-				return;
-			};
-			if index.len() > 2 {
-				let first_remove = index.iter().position(|it| it.typ == DiffType::Remove);
-				let last_add = index.iter().rposition(|it| it.typ == DiffType::Add);
-				if let (Some(first), Some(last)) = (first_remove, last_add) {
-					if let (Some(left), Some(right)) = (self.get_index(patch, first), self.get_index(patch, last)) {
-						if left.drop(1) == right.drop(1) {
-							println!("SWAP");
-							swap_line.0 = first;
-							swap_line.1 = last;
-							swap_idx.0 = index.get(first).unwrap().start;
-							swap_idx.1 = index.get(last).unwrap().start;
-						}
+	/// Line numbers (starting from 1) of new file which are added by this patch
+	pub fn added_lines(&self) -> Vec<u64> {
+		let mut lines = Vec::new();
+		for (offset, index) in &self.index {
+			let mut line = offset.target_start;
+			for it in index {
+				match it.typ {
+					DiffType::Add => {
+						lines.push(line);
+						line += 1;
 					}
+					DiffType::None => line += 1,
+					DiffType::Remove | DiffType::NoNewline => {}
 				}
 			}
 		}
+		lines
+	}
+
+	/// Apply patch to `content`, hunks may be found away from their recorded position
+	/// and up to [DEFAULT_FUZZ] context lines at both ends of a hunk may not match
+	pub fn apply(&self, content: &str) -> Result<String, ApplyError> {
+		self.apply_hunks(content, false, DEFAULT_FUZZ)
+	}
 
-		if swap_line.1 != 0 && swap_idx.1 != 0 {
-			self.raw_diff.replace_range(swap_idx.0..=swap_idx.0, " ");
-			self.raw_diff.replace_range(swap_idx.1..=swap_idx.1, "+");
-			if let Some((_, index)) = self.index.get_mut(patch) {
-				index.get_mut(swap_line.0).unwrap().typ = DiffType::None;
-				index.get_mut(swap_line.1).unwrap().typ = DiffType::Add;
+	/// Undo patch applied to `content`
+	pub fn reverse_apply(&self, content: &str) -> Result<String, ApplyError> {
+		self.apply_hunks(content, true, DEFAULT_FUZZ)
+	}
+
+	/// Apply every hunk with at most `max_fuzz` ignored context lines
+	pub fn apply_hunks(&self, content: &str, reverse: bool, max_fuzz: usize) -> Result<String, ApplyError> {
+		let lines = content.split_inclusive('\n').collect::<Vec<_>>();
+		let mut output = String::with_capacity(content.len());
+		// next line of `content` to copy
+		let mut next = 0;
+		// distance between recorded and found position of previous hunk
+		let mut drift = 0i64;
+		for (no, (offset, index)) in self.index.iter().enumerate() {
+			let hunk = HunkImage::new(&self.raw_diff, index, reverse);
+			let (start, count) = match reverse {
+				false => (offset.source_start, offset.source_lines),
+				true => (offset.target_start, offset.target_lines),
+			};
+			// empty range start after recorded line
+			let expected = start as i64 - (count > 0) as i64 + drift;
+			let found = (0..=max_fuzz).find_map(|fuzz| {
+				let head = fuzz.min(hunk.leading);
+				let tail = fuzz.min(hunk.trailing);
+				let before = &hunk.before[head..hunk.before.len() - tail];
+				let at = find_lines(&lines, before, next, expected + head as i64)?;
+				Some((at, head, tail, before.len()))
+			});
+			let Some((at, head, tail, len)) = found else {
+				return Err(ApplyError::Conflict {
+					hunk: no + 1,
+					header: offset.to_string(),
+					line: expected.max(0) as u64 + 1,
+				});
 			};
+			drift = at as i64 - head as i64 - (start as i64 - (count > 0) as i64);
+			lines[next..at].iter().for_each(|it| output.push_str(it));
+			hunk.after[head..hunk.after.len() - tail].iter().for_each(|it| output.push_str(it));
+			next = at + len;
 		}
+		lines[next..].iter().for_each(|it| output.push_str(it));
+		Ok(output)
 	}
+
+	/// Slide ambiguous changes of every hunk the same way as [Patch::diff],
+	/// for patches made without git's indent heuristic, only lines inside each hunk are considered
+	pub fn normalize(&mut self) {
+		let mut raw_diff = String::with_capacity(self.raw_diff.len());
+		for (_, index) in &mut self.index {
+			let (mut old, mut new, mut edits) = (Vec::new(), Vec::new(), Vec::new());
+			for (i, line) in index.iter().enumerate() {
+				let mut text = self.raw_diff[line.start + 1..line.end].to_string();
+				if index.get(i + 1).is_none_or(|it| it.typ != DiffType::NoNewline) {
+					text.push('\n');
+				}
+				match line.typ {
+					DiffType::None => {
+						old.push(text.clone());
+						new.push(text);
+						edits.push(Edit::Equal);
+					}
+					DiffType::Remove => {
+						old.push(text);
+						edits.push(Edit::Delete);
+					}
+					DiffType::Add => {
+						new.push(text);
+						edits.push(Edit::Insert);
+					}
+					DiffType::NoNewline => {}
+				}
+			}
+			let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+			let new = new.iter().map(String::as_str).collect::<Vec<_>>();
+			index.clear();
+			let (mut o, mut n) = (0, 0);
+			for edit in compact(&old, &new, &edits) {
+				push_line(&mut raw_diff, index, edit, &old, &new, &mut o, &mut n);
+			}
+		}
+		self.raw_diff = raw_diff;
+	}
+}
+
+/// Append line of `edit` with its prefix and no newline marker, advance positions in `old` and `new`
+fn push_line(raw_diff: &mut String, patch: &mut Vec<PatchIndex>, edit: Edit, old: &[&str], new: &[&str], o: &mut usize, n: &mut usize) {
+	let (typ, prefix, line) = match edit {
+		Edit::Equal => (DiffType::None, ' ', old[*o]),
+		Edit::Delete => (DiffType::Remove, '-', old[*o]),
+		Edit::Insert => (DiffType::Add, '+', new[*n]),
+	};
+	if edit != Edit::Insert {
+		*o += 1;
+	}
+	if edit != Edit::Delete {
+		*n += 1;
+	}
+	let start = raw_diff.len();
+	raw_diff.push(prefix);
+	raw_diff.push_str(line.strip_suffix('\n').unwrap_or(line));
+	patch.push(PatchIndex { typ, start, end: raw_diff.len() });
+	raw_diff.push('\n');
+	if !line.ends_with('\n') {
+		let start = raw_diff.len();
+		raw_diff.push_str(NO_NEWLINE);
+		patch.push(PatchIndex { typ: DiffType::NoNewline, start, end: raw_diff.len() });
+		raw_diff.push('\n');
+	}
+}
+
+/// Number of context lines which may not match when applying a hunk, same as GNU patch
+pub const DEFAULT_FUZZ: usize = 2;
+
+/// Lines of a hunk before and after it is applied, including line terminator
+struct HunkImage {
+	before: Vec<String>,
+	after: Vec<String>,
+	/// Number of context lines before first change
+	leading: usize,
+	/// Number of context lines after last change
+	trailing: usize,
+}
+
+impl HunkImage {
+	fn new(raw_diff: &str, index: &[PatchIndex], reverse: bool) -> Self {
+		let mut image = HunkImage {
+			before: Vec::with_capacity(index.len()),
+			after: Vec::with_capacity(index.len()),
+			leading: 0,
+			trailing: 0,
+		};
+		let mut changed = false;
+		for (i, line) in index.iter().enumerate() {
+			let mut text = raw_diff[line.start + 1..line.end].to_string();
+			if index.get(i + 1).is_none_or(|it| it.typ != DiffType::NoNewline) {
+				text.push('\n');
+			}
+			match (line.typ, reverse) {
+				(DiffType::None, _) => {
+					image.before.push(text.clone());
+					image.after.push(text);
+					if changed {
+						image.trailing += 1;
+					} else {
+						image.leading += 1;
+					}
+				}
+				(DiffType::Remove, false) | (DiffType::Add, true) => {
+					image.before.push(text);
+					changed = true;
+					image.trailing = 0;
+				}
+				(DiffType::Add, false) | (DiffType::Remove, true) => {
+					image.after.push(text);
+					changed = true;
+					image.trailing = 0;
+				}
+				(DiffType::NoNewline, _) => {}
+			}
+		}
+		image
+	}
+}
+
+/// Find `needle` in `lines` not before `from`, nearest to `expected` first
+fn find_lines(lines: &[&str], needle: &[String], from: usize, expected: i64) -> Option<usize> {
+	let last = lines.len().checked_sub(needle.len())?;
+	if from > last {
+		return None;
+	}
+	let expected = expected.clamp(from as i64, last as i64) as usize;
+	let matches = |at: usize| lines[at..at + needle.len()].iter().zip(needle).all(|(a, b)| *a == b);
+	(0..=(last - from).max(expected - from)).find_map(|distance| {
+		[expected.checked_add(distance), expected.checked_sub(distance)].into_iter()
+			.flatten()
+			.find(|at| (from..=last).contains(at) && matches(*at))
+	})
+}
+
+/// Marker following a line which is not terminated by newline
+const NO_NEWLINE: &str = "\\ No newline at end of file";
+
+/// Last line before hunk which look like start of a function (git default `funcname`)
+fn section_heading(before: &[&str]) -> Option<String> {
+	before.iter().rev()
+		.find(|it| it.starts_with(|ch: char| ch.is_alphabetic() || ch == '_' || ch == '$'))
+		.map(|it| it.trim_end().chars().take(80).collect())
+}
+
+/// Line of a hunk with its line numbers, see [PatchInfo::lines]
+#[derive(Debug, Serialize)]
+pub struct HunkLine<'a> {
+	#[serde(rename = "type")]
+	pub typ: DiffType,
+	/// Line number before change, `None` for added line
+	pub old: Option<u64>,
+	/// Line number after change, `None` for removed line
+	pub new: Option<u64>,
+	/// Content without `+`, `-` or ` ` prefix
+	pub content: &'a str,
+	/// Followed by `\ No newline at end of file`
+	pub missing_newline: bool,
 }
 
 #[derive(Debug)]
@@ -394,10 +959,63 @@ impl<'a> PatchInfo<'a> {
 		Some(&self.contents[range])
 	}
 
+	pub fn line_type(&self, line: usize) -> Option<DiffType> {
+		self.index.get(line).map(|it| it.typ)
+	}
+
+	/// `true` if `line` is followed by `\ No newline at end of file`
+	pub fn missing_newline(&self, line: usize) -> bool {
+		self.line_type(line + 1) == Some(DiffType::NoNewline)
+	}
+
+	/// Index of added line replacing removed `line` or the other way around,
+	/// n-th removed line of a change is paired with its n-th added line
+	pub fn paired_line(&self, line: usize) -> Option<usize> {
+		let typ = self.line_type(line).filter(|it| matches!(it, DiffType::Remove | DiffType::Add))?;
+		let is_change = |at: usize| matches!(self.line_type(at), Some(DiffType::Remove | DiffType::Add | DiffType::NoNewline));
+		let mut start = line;
+		while start > 0 && is_change(start - 1) {
+			start -= 1;
+		}
+		let mut end = line + 1;
+		while is_change(end) {
+			end += 1;
+		}
+		let lines_of = |typ: DiffType| (start..end).filter(|it| self.line_type(*it) == Some(typ)).collect::<Vec<_>>();
+		let (removed, added) = (lines_of(DiffType::Remove), lines_of(DiffType::Add));
+		let (this, other) = match typ {
+			DiffType::Remove => (removed, added),
+			_ => (added, removed),
+		};
+		let at = this.iter().position(|it| *it == line)?;
+		other.get(at).copied()
+	}
+
+	/// Word level comparison of `line` and its paired line, see [Self::paired_line]
+	pub fn word_diff(&self, line: usize) -> Option<WordDiff<'_>> {
+		let other = self.paired_line(line)?;
+		let (old, new) = match self.line_type(line)? {
+			DiffType::Remove => (line, other),
+			_ => (other, line),
+		};
+		WordDiff::new(&self.get_line(old)?[1..], &self.get_line(new)?[1..])
+	}
+
+	/// Changed byte ranges of `line` as returned by [Self::get_line],
+	/// `None` if line is not paired or barely similar to its pair
+	pub fn word_spans(&self, line: usize) -> Option<Vec<Range<usize>>> {
+		let diff = self.word_diff(line)?;
+		let spans = match self.line_type(line)? {
+			DiffType::Remove => diff.old_spans(),
+			_ => diff.new_spans(),
+		};
+		Some(spans.into_iter().map(|it| it.start + 1..it.end + 1).collect())
+	}
+
 	pub fn is_valid(&self) -> bool {
 		let DiffOffset { source_lines, target_lines, .. } = self.offset;
 		self.source_lines() as u64 == *source_lines
-			&& self.patch_lines() as u64 == *target_lines
+			&& self.output_lines() as u64 == *target_lines
 	}
 
 	pub fn source_lines(&self) -> usize {
@@ -405,6 +1023,7 @@ impl<'a> PatchInfo<'a> {
 			DiffType::Add => r,
 			DiffType::Remove => r + 1,
 			DiffType::None => r + 1,
+			DiffType::NoNewline => r,
 		}) as _
 	}
 
@@ -412,25 +1031,73 @@ impl<'a> PatchInfo<'a> {
 		self.index.iter().fold(0i128, |r, it| match it.typ {
 			DiffType::Add => r + 1,
 			DiffType::Remove => r,
-			DiffType::None => r,
+			DiffType::None => r + 1,
+			DiffType::NoNewline => r,
 		}) as _
 	}
 
 	pub fn patch_lines(&self) -> usize {
 		self.index.len()
 	}
+
+	/// Lines with their numbers, `\ No newline at end of file` markers become flag of line before them
+	pub fn lines(&self) -> Vec<HunkLine<'_>> {
+		let (mut old, mut new) = (self.offset.source_start, self.offset.target_start);
+		let mut lines = Vec::with_capacity(self.index.len());
+		for line in 0..self.index.len() {
+			let (Some(typ), Some(text)) = (self.line_type(line), self.get_line(line)) else { continue };
+			let (old_no, new_no) = match typ {
+				DiffType::None => (Some(old), Some(new)),
+				DiffType::Remove => (Some(old), None),
+				DiffType::Add => (None, Some(new)),
+				DiffType::NoNewline => continue,
+			};
+			old += old_no.is_some() as u64;
+			new += new_no.is_some() as u64;
+			lines.push(HunkLine {
+				typ,
+				old: old_no,
+				new: new_no,
+				content: text.get(1..).unwrap_or_default(),
+				missing_newline: self.missing_newline(line),
+			});
+		}
+		lines
+	}
+}
+
+/// Hunk as its header and numbered lines
+impl Serialize for PatchInfo<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut hunk = serializer.serialize_struct("Hunk", 2)?;
+		hunk.serialize_field("offset", self.offset)?;
+		hunk.serialize_field("lines", &self.lines())?;
+		hunk.end()
+	}
+}
+
+/// Every hunk as [PatchInfo]
+impl Serialize for Patch {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_seq((0..self.patches()).filter_map(|it| self.get_patch(it)))
+	}
 }
 
 #[cfg(test)]
 mod test_data {
-	use crate::git::Patch;
+	use crate::error::{ApplyError, ParseErrorKind, StepdocsError};
+	use crate::git::{ChangeKind, DiffAlgorithm, DiffOffset, DiffOptions, DiffType, GitDiffParser, Patch};
+	use crate::util::iter::AsyncIterator;
 
+	use super::{parse_command_paths, unquote};
+
+	#[allow(dead_code)]
 	const SHOULD_NORMALIZE_PATCH: &str = r#"-}
 +
 + hello
 +}"#;
 
-	const PATCH_SINGLE: &'static str = r#"@@ -1,8 +1,13 @@
+	const PATCH_SINGLE: &str = r#"@@ -1,8 +1,13 @@
 +use std::future::Future;
  use std::io;
 +use std::io::Error;
@@ -458,7 +1125,7 @@ mod test_data {
 +impl AsyncIterator<io::Error> for GitLogParser {
 +       type Item = GitLog;"#;
 
-	const DIFF_MULTIPLE: &'static str = r#"diff --git a/src/git/log_parser.rs b/src/git/log_parser.rs
+	const DIFF_MULTIPLE: &str = r#"diff --git a/src/git/log_parser.rs b/src/git/log_parser.rs
 index c5a6ad4..8ea4982 100644
 --- a/src/git/log_parser.rs
 +++ b/src/git/log_parser.rs
@@ -598,6 +1265,228 @@ index e84d72c..deb7906 100644
 \ No newline at end of file
 "#;
 
+	const DIFF_EXTENDED: &str = r#"diff --git a/old.rs b/new.rs
+similarity index 90%
+rename from old.rs
+rename to new.rs
+index 1111111..2222222 100644
+--- a/old.rs
++++ b/new.rs
+@@ -1,2 +1,2 @@
+ fn main() {
+-}
++ }
+diff --git a/moved.txt b/dir/moved.txt
+similarity index 100%
+rename from moved.txt
+rename to dir/moved.txt
+diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index 3333333..0000000
+--- a/gone.txt
++++ /dev/null
+@@ -1,1 +0,0 @@
+-bye
+diff --git a/copy.rs b/copy.rs
+new file mode 100644
+index 0000000..4444444
+--- /dev/null
++++ b/copy.rs
+@@ -0,0 +1,1 @@
++fn copy() {}
+diff --git a/new.rs b/copied.rs
+similarity index 100%
+copy from new.rs
+copy to copied.rs
+"#;
+
+	#[tokio::test]
+	async fn test_parse_multiple() {
+		let diffs = GitDiffParser::from_reader(DIFF_MULTIPLE.as_bytes()).collect().await.unwrap();
+		assert_eq!(diffs.len(), 3);
+		assert_eq!(diffs[0].path(), "src/git/log_parser.rs");
+		assert_eq!(diffs[0].diffs.patches(), 2);
+		assert_eq!(diffs[1].path(), "src/main.rs");
+		assert_eq!(diffs[2].path(), "src/util/iter.rs");
+		assert!(diffs.iter().all(|it| it.kind == ChangeKind::Modified));
+		assert_eq!(diffs[2].old_mode, Some(0o100644));
+
+		let last = diffs[2].diffs.get_patch(1).unwrap();
+		let marker = last.patch_lines() - 1;
+		assert_eq!(last.line_type(marker), Some(DiffType::NoNewline));
+		assert!(last.missing_newline(marker - 1));
+		assert!(last.is_valid());
+	}
+
+	#[tokio::test]
+	async fn test_parse_extended_header() {
+		let diffs = GitDiffParser::from_reader(DIFF_EXTENDED.as_bytes()).collect().await.unwrap();
+		let kinds = diffs.iter().map(|it| it.kind).collect::<Vec<_>>();
+		assert_eq!(kinds, [
+			ChangeKind::Renamed,
+			ChangeKind::Renamed,
+			ChangeKind::ModeChange,
+			ChangeKind::Deleted,
+			ChangeKind::Added,
+			ChangeKind::Copied,
+		]);
+
+		assert_eq!(diffs[0].old_path.as_deref(), Some("old.rs"));
+		assert_eq!(diffs[0].new_path.as_deref(), Some("new.rs"));
+		assert_eq!(diffs[0].similarity, Some(90));
+		assert_eq!(diffs[0].diffs.patches(), 1);
+
+		assert_eq!(diffs[1].path(), "dir/moved.txt");
+		assert_eq!(diffs[1].diffs.patches(), 0);
+
+		assert_eq!(diffs[2].old_mode, Some(0o100644));
+		assert_eq!(diffs[2].new_mode, Some(0o100755));
+
+		assert_eq!(diffs[3].new_path, None);
+		assert_eq!(diffs[3].path(), "gone.txt");
+
+		assert_eq!(diffs[4].old_path, None);
+		assert_eq!(diffs[4].new_mode, Some(0o100644));
+
+		assert_eq!(diffs[5].old_path.as_deref(), Some("new.rs"));
+		assert_eq!(diffs[5].path(), "copied.rs");
+	}
+
+	const DIFF_BINARY: &str = "diff --git a/logo.png b/logo.png
+new file mode 100644
+index 0000000000000000000000000000000000000000..ac6c5447f9db7f0df9199b469c3dd025c5b92d6c
+GIT binary patch
+literal 16
+XcmeAS@N?(olHy`uNXpDhEUE+mASeVc
+
+literal 0
+HcmV?d00001
+
+diff --git a/shot.jpg b/shot.jpg
+index 1234567..89abcde 100644
+Binary files a/shot.jpg and b/shot.jpg differ
+diff --git a/a.txt b/a.txt
+index 1111111..2222222 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1,1 +1,1 @@
+-a
++b
+";
+
+	#[tokio::test]
+	async fn test_parse_binary() {
+		let diffs = GitDiffParser::from_reader(DIFF_BINARY.as_bytes()).collect().await.unwrap();
+		assert_eq!(diffs.len(), 3);
+
+		let binary = diffs[0].binary.as_ref().unwrap();
+		assert_eq!(diffs[0].kind, ChangeKind::Added);
+		assert_eq!(binary.old_id, None);
+		assert_eq!(binary.new_id.as_deref(), Some("ac6c5447f9db7f0df9199b469c3dd025c5b92d6c"));
+		assert_eq!(binary.new_size, Some(16));
+		assert_eq!(binary.old_size, Some(0));
+
+		let binary = diffs[1].binary.as_ref().unwrap();
+		assert_eq!(diffs[1].kind, ChangeKind::Modified);
+		assert_eq!(binary.old_id.as_deref(), Some("1234567"));
+		assert_eq!(binary.new_id.as_deref(), Some("89abcde"));
+		assert_eq!(binary.new_size, None);
+
+		assert!(!diffs[2].is_binary());
+		assert_eq!(diffs[2].diffs.patches(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_parse_quoted_path() {
+		let diff = "diff --git \"a/\\303\\274 b.txt\" \"b/\\303\\266.txt\"\n\
+			similarity index 100%\n\
+			rename from \"\\303\\274 b.txt\"\n\
+			rename to \"\\303\\266.txt\"\n\
+			diff --git \"a/tab\\there.txt\" \"b/tab\\there.txt\"\n\
+			index 587be6b..5c17d8d 100644\n\
+			--- \"a/tab\\there.txt\"\n\
+			+++ \"b/tab\\there.txt\"\n\
+			@@ -1 +1,2 @@\n x\n+2\n";
+		let diffs = GitDiffParser::from_reader(diff.as_bytes()).collect().await.unwrap();
+		assert_eq!(diffs.len(), 2);
+		assert_eq!(diffs[0].kind, ChangeKind::Renamed);
+		assert_eq!(diffs[0].old_path.as_deref(), Some("\u{fc} b.txt"));
+		assert_eq!(diffs[0].new_path.as_deref(), Some("\u{f6}.txt"));
+		assert_eq!(diffs[1].old_path.as_deref(), Some("tab\there.txt"));
+		assert_eq!(diffs[1].new_path.as_deref(), Some("tab\there.txt"));
+		assert_eq!(diffs[1].diffs.patches(), 1);
+
+		assert_eq!(parse_command_paths("diff --git \"a/x\\\"y\" b/z"), (Some("x\"y".to_string()), Some("z".to_string())));
+		assert_eq!(unquote("\"a\\nb\\\\c\\1\\1011\""), "a\nb\\c\u{1}A1");
+	}
+
+	#[test]
+	fn test_patch_no_newline() {
+		let patch = Patch::parse("@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n\\ No newline at end of file\n".to_string()).unwrap();
+		let inner = patch.get_patch(0).unwrap();
+		assert_eq!(inner.patch_lines(), 5);
+		assert!(inner.missing_newline(1));
+		assert!(inner.missing_newline(3));
+		assert!(!inner.missing_newline(0));
+		assert_eq!(inner.get_line(2), Some("\\ No newline at end of file"));
+		assert!(inner.is_valid());
+	}
+
+	#[tokio::test]
+	async fn test_parse_error() {
+		let truncated = "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,3 @@\n a\n-b\n";
+		let err = GitDiffParser::from_reader(truncated.as_bytes())
+			.with_commit("abc")
+			.collect()
+			.await
+			.unwrap_err();
+		let StepdocsError::Parse(err) = err else { panic!("{err:?}") };
+		assert_eq!(err.kind, ParseErrorKind::HunkLength);
+		assert_eq!(err.commit.as_deref(), Some("abc"));
+		assert_eq!(err.file.as_deref(), Some("a.txt"));
+
+		let corrupted = "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1,1 +1,1 @@\n?a\n";
+		let err = GitDiffParser::from_reader(corrupted.as_bytes()).collect().await.unwrap_err();
+		let StepdocsError::Parse(err) = err else { panic!("{err:?}") };
+		assert_eq!(err.kind, ParseErrorKind::HunkLine);
+		assert_eq!(err.line, 5);
+		assert_eq!(err.text, "?a");
+
+		let err = Patch::parse("@@ -1,1 +1,1 @@\n a\n@@ -x +y @@\n".to_string()).unwrap_err();
+		assert_eq!(err.kind, ParseErrorKind::HunkHeader);
+		assert_eq!(err.line, 3);
+	}
+
+	#[test]
+	fn test_offset() {
+		let offset = DiffOffset::parse("@@ -5 +5 @@").unwrap();
+		assert_eq!((offset.source_start, offset.source_lines, offset.target_start, offset.target_lines), (5, 1, 5, 1));
+		assert_eq!(offset.section_heading, None);
+		assert_eq!(offset.to_string(), "@@ -5 +5 @@");
+
+		let offset = DiffOffset::parse("@@ -0,0 +1,3 @@").unwrap();
+		assert_eq!((offset.source_start, offset.source_lines, offset.target_start, offset.target_lines), (0, 0, 1, 3));
+
+		let offset = DiffOffset::parse("@@ -21,10 +26,20 @@ macro_rules! read_or_none {").unwrap();
+		assert_eq!(offset.section_heading.as_deref(), Some("macro_rules! read_or_none {"));
+		assert_eq!(offset.to_string(), "@@ -21,10 +26,20 @@ macro_rules! read_or_none {");
+
+		assert!(DiffOffset::parse("@@ -1,2 @@").is_err());
+		assert!(DiffOffset::parse("@@ 1,2 +1,2 @@").is_err());
+		assert!(DiffOffset::parse("@@ -1,2 +1,2").is_err());
+	}
+
+	#[test]
+	fn test_patch_section_heading() {
+		let patch = Patch::parse(PATCH_SINGLE.to_string()).unwrap();
+		let second = patch.get_patch(1).unwrap();
+		assert_eq!(second.offset.section_heading.as_deref(), Some("macro_rules! read_or_none {"));
+		assert!(second.get_line(0).unwrap().ends_with("if 0 == $self.inner.read_line(&mut $line).await? {"));
+	}
+
 	#[test]
 	fn test_patch() {
 		let patch = Patch::parse(PATCH_SINGLE.to_string());
@@ -611,4 +1500,95 @@ index e84d72c..deb7906 100644
 		println!("{:?}", inner);
 		println!("{:#?}", patch);
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn test_patch_diff() {
+		let old = "fn main() {\n\tlet a = 1;\n\tlet b = 2;\n\tlet c = 3;\n\tlet d = 4;\n\tlet e = 5;\n\tlet f = 6;\n\tlet g = 7;\n\tlet h = 8;\n}";
+		let new = "fn main() {\n\tlet a = 10;\n\tlet b = 2;\n\tlet c = 3;\n\tlet d = 4;\n\tlet e = 5;\n\tlet f = 6;\n\tlet g = 7;\n\tlet h = 8;\n}\n";
+		for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Patience, DiffAlgorithm::Histogram] {
+			let patch = Patch::diff(old, new, &DiffOptions { algorithm, context: 3 });
+			assert_eq!(patch.patches(), 2);
+			let first = patch.get_patch(0).unwrap();
+			assert!(first.is_valid());
+			assert_eq!(first.offset.to_string(), "@@ -1,5 +1,5 @@");
+			assert_eq!(first.get_line(1), Some("-\tlet a = 1;"));
+			assert_eq!(first.get_line(2), Some("+\tlet a = 10;"));
+
+			let last = patch.get_patch(1).unwrap();
+			assert!(last.is_valid());
+			assert_eq!(last.offset.to_string(), "@@ -7,4 +7,4 @@ fn main() {");
+			assert_eq!(last.line_type(3), Some(DiffType::Remove));
+			assert!(last.missing_newline(3));
+			assert_eq!(last.get_line(5), Some("+}"));
+		}
+
+		// changes closer than two contexts share a hunk
+		let patch = Patch::diff(old, new, &DiffOptions { algorithm: DiffAlgorithm::Myers, context: 4 });
+		assert_eq!(patch.patches(), 1);
+		assert!(patch.get_patch(0).unwrap().is_valid());
+
+		let patch = Patch::diff("", "a\n", &DiffOptions::default());
+		assert_eq!(patch.get_patch(0).unwrap().offset.to_string(), "@@ -0,0 +1 @@");
+		assert_eq!(Patch::diff("a\n", "a\n", &DiffOptions::default()).patches(), 0);
+	}
+
+	#[test]
+	fn test_patch_apply() {
+		let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj";
+		let new = "a\nb\nc\nD\ne\nf\ng\nh\ni\nj\n";
+		let patch = Patch::diff(old, new, &DiffOptions::default());
+		assert_eq!(patch.apply(old).unwrap(), new);
+		assert_eq!(patch.reverse_apply(new).unwrap(), old);
+
+		// hunks moved by lines added before them
+		let moved = format!("x\ny\n{old}");
+		assert_eq!(patch.apply(&moved).unwrap(), format!("x\ny\n{new}"));
+
+		// context line changed near the edge is tolerated by fuzz
+		let fuzzy = old.replacen("b\n", "B\n", 1);
+		assert_eq!(patch.apply(&fuzzy).unwrap(), new.replacen("b\n", "B\n", 1));
+		assert!(patch.apply_hunks(&fuzzy, false, 0).is_err());
+
+		// removed line is missing
+		let err = patch.apply(&old.replace("d\n", "z\n")).unwrap_err();
+		assert_eq!(err, ApplyError::Conflict { hunk: 1, header: "@@ -1,10 +1,10 @@".to_string(), line: 1 });
+		// already applied
+		assert!(patch.apply(new).is_err());
+
+		assert_eq!(patch.added_lines(), vec![4, 10]);
+
+		let added = Patch::diff("", "a\nb\n", &DiffOptions::default());
+		assert_eq!(added.apply("").unwrap(), "a\nb\n");
+		assert_eq!(added.reverse_apply("a\nb\n").unwrap(), "");
+	}
+
+	#[test]
+	fn test_patch_word_spans() {
+		let old = "fn main() {\n\tlet total = price * count;\n\tprintln!();\n}\n";
+		let new = "fn main() {\n\tlet total = price * amount;\n\tdbg!(total);\n\tprintln!();\n}\n";
+		let patch = Patch::diff(old, new, &DiffOptions::default());
+		let hunk = patch.get_patch(0).unwrap();
+		assert_eq!(hunk.line_type(1), Some(DiffType::Remove));
+		assert_eq!(hunk.paired_line(1), Some(2));
+		assert_eq!(hunk.paired_line(2), Some(1));
+		// added line without removed counterpart
+		assert_eq!(hunk.paired_line(3), None);
+		assert_eq!(hunk.paired_line(0), None);
+
+		let spans = hunk.word_spans(1).unwrap();
+		assert_eq!((spans.len(), spans[0].clone()), (1, 22..27));
+		assert_eq!(&hunk.get_line(2).unwrap()[22..28], "amount");
+		assert_eq!(hunk.word_spans(3), None);
+	}
+
+	#[test]
+	fn test_patch_normalize() {
+		// as produced by `git diff --no-indent-heuristic`
+		let diff = "@@ -1,5 +1,8 @@\n fn a() {\n+}\n+\n+fn b() {\n }\n \n fn c() {\n }\n";
+		let mut patch = Patch::parse(diff.to_string()).unwrap();
+		patch.normalize();
+		let hunk = patch.get_patch(0).unwrap();
+		assert_eq!(hunk.contents, " fn a() {\n }\n \n+fn b() {\n+}\n+\n fn c() {\n }\n");
+		assert!(hunk.is_valid());
+	}
+}
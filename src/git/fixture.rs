@@ -0,0 +1,67 @@
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Temporary repository built by `git` binary for tests, removed when dropped
+pub(crate) struct TestRepo {
+	path: PathBuf,
+	/// Commits made so far, every commit is one minute after previous one
+	commits: Cell<u64>,
+}
+
+impl TestRepo {
+	/// Empty repository with `main` branch
+	pub fn new(name: &str) -> Self {
+		let path = std::env::temp_dir().join(format!("stepdocs-{name}-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&path);
+		std::fs::create_dir_all(&path).unwrap();
+		let repo = Self { path, commits: Cell::new(0) };
+		repo.git(&["init", "-q", "-b", "main"]);
+		repo
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Run git command and return its trimmed stdout, panic when it fails
+	pub fn git(&self, args: &[&str]) -> String {
+		let time = format!("{} +0000", 1700000000 + self.commits.get() * 60);
+		let output = Command::new("git")
+			.args(args)
+			.current_dir(&self.path)
+			.env("GIT_CONFIG_NOSYSTEM", "1")
+			.env("GIT_CONFIG_GLOBAL", "/dev/null")
+			.env("GIT_AUTHOR_NAME", "Author")
+			.env("GIT_AUTHOR_EMAIL", "author@example.com")
+			.env("GIT_COMMITTER_NAME", "Committer")
+			.env("GIT_COMMITTER_EMAIL", "committer@example.com")
+			.env("GIT_AUTHOR_DATE", &time)
+			.env("GIT_COMMITTER_DATE", &time)
+			.output()
+			.unwrap();
+		assert!(output.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&output.stderr));
+		String::from_utf8_lossy(&output.stdout).trim().to_string()
+	}
+
+	/// Write file at `path` relative to work tree
+	pub fn write(&self, path: &str, content: &str) {
+		let path = self.path.join(path);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, content).unwrap();
+	}
+
+	/// Commit every change in work tree and return its hash
+	pub fn commit(&self, message: &str) -> String {
+		self.commits.set(self.commits.get() + 1);
+		self.git(&["add", "-A"]);
+		self.git(&["commit", "-q", "--allow-empty", "-m", message]);
+		self.git(&["rev-parse", "HEAD"])
+	}
+}
+
+impl Drop for TestRepo {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.path);
+	}
+}
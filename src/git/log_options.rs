@@ -0,0 +1,114 @@
+use crate::error::{Result, StepdocsError};
+use crate::git::log_parser::LOG_FORMAT;
+
+/// Order of commits listed by [crate::git::GitRepository::log]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum LogOrder {
+	/// Newest committer date first, like `git log`
+	#[default]
+	Date,
+	/// Children before parents without intermixing lines of history, like `git log --topo-order`
+	Topo,
+}
+
+/// Commits listed by [crate::git::GitRepository::log]
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct LogOptions {
+	/// Revisions separated by whitespace such as `main`, `v1..v2`, `main~10..main`, `v1...feat` or `main ^v1`,
+	/// every ref when `None`
+	pub range: Option<String>,
+	pub order: LogOrder,
+	/// Oldest commit first, applied after [Self::order]
+	pub reverse: bool,
+	/// Only follow first parent of merge commits
+	pub first_parent: bool,
+	/// Only commits changing any of these paths relative to repository root
+	pub paths: Vec<String>,
+}
+
+impl LogOptions {
+	/// Every commit reachable from any ref, newest first
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn range(mut self, range: impl Into<String>) -> Self {
+		self.range = Some(range.into());
+		self
+	}
+
+	pub fn order(mut self, order: LogOrder) -> Self {
+		self.order = order;
+		self
+	}
+
+	pub fn reverse(mut self, reverse: bool) -> Self {
+		self.reverse = reverse;
+		self
+	}
+
+	pub fn first_parent(mut self, first_parent: bool) -> Self {
+		self.first_parent = first_parent;
+		self
+	}
+
+	/// Add path limiting listed commits, a directory matches every file below it
+	pub fn path(mut self, path: impl Into<String>) -> Self {
+		self.paths.push(path.into());
+		self
+	}
+
+	/// Revisions of [Self::range], `--all` when not set,
+	/// a revision starting with `-` is rejected so range from config can't inject `git` options
+	pub fn revisions(&self) -> Result<Vec<&str>> {
+		let Some(range) = self.range.as_deref() else {
+			return Ok(vec!["--all"]);
+		};
+		range.split_whitespace()
+			.map(|it| if it.starts_with('-') { Err(StepdocsError::NotFound(it.to_string())) } else { Ok(it) })
+			.collect()
+	}
+
+	/// Arguments of `git log` printing commits in [LOG_FORMAT]
+	pub(crate) fn args(&self) -> Result<Vec<String>> {
+		let mut args = vec!["log".to_string(), "--date=raw".to_string(), format!("--pretty={LOG_FORMAT}")];
+		if self.order == LogOrder::Topo {
+			args.push("--topo-order".to_string());
+		}
+		if self.reverse {
+			args.push("--reverse".to_string());
+		}
+		if self.first_parent {
+			args.push("--first-parent".to_string());
+		}
+		args.extend(self.revisions()?.into_iter().map(str::to_string));
+		args.push("--".to_string());
+		args.extend(self.paths.iter().cloned());
+		Ok(args)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::git::{LogOptions, LogOrder};
+
+	#[test]
+	fn test_args() {
+		let args = LogOptions::new().args().unwrap();
+		assert_eq!(&args[3..], ["--all", "--"]);
+		let args = LogOptions::new()
+			.range("main~10..main  ^v1")
+			.order(LogOrder::Topo)
+			.reverse(true)
+			.first_parent(true)
+			.path("src/")
+			.args()
+			.unwrap();
+		assert_eq!(&args[3..], ["--topo-order", "--reverse", "--first-parent", "main~10..main", "^v1", "--", "src/"]);
+
+		// options can't be passed as revisions
+		for range in ["--output=/tmp/pwned", "main -p", "--all"] {
+			assert!(LogOptions::new().range(range).args().is_err(), "{range}");
+		}
+	}
+}
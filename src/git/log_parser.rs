@@ -1,77 +1,329 @@
+use std::fmt::{Display, Formatter};
 use std::future::Future;
-use std::io;
-use std::io::Error;
 use std::pin::Pin;
 
-use tokio::io::AsyncBufReadExt;
-use tokio::io::BufReader;
+use serde::Serialize;
+use tokio::io::{AsyncRead, BufReader};
 use tokio::process::{Child, ChildStdout};
 
-use crate::read_or_none;
+use crate::error::{ParseError, ParseErrorKind, Result, StepdocsError};
+use crate::git::GitTime;
 use crate::util::iter::AsyncIterator;
+use crate::util::peekable_reader::PeekableLine;
+use crate::util::proc::check_exit;
+use crate::{read_or_eof, read_or_none};
 
-pub struct GitLogParser {
-	_child: Child,
-	inner: BufReader<ChildStdout>,
+pub struct GitLogParser<R = ChildStdout> {
+	child: Option<Child>,
+	inner: PeekableLine<R>,
 }
 
-#[derive(Debug)]
+/// `git log --pretty` format understood by [GitLogParser], dates must be printed with `--date=raw`,
+/// names and emails are mapped by `.mailmap`, message is terminated by NUL which cannot appear in it
+pub const LOG_FORMAT: &str = "format:%H%n%P%n%aN%n%aE%n%ad%n%cN%n%cE%n%cd%n%B%x00";
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct GitLog {
 	pub hash: String,
-	pub author: String,
+	/// Parent hashes, empty for root commit
+	pub parents: Vec<String>,
+	pub author: Signature,
+	pub committer: Signature,
+	/// First line of commit message
+	pub subject: String,
+	/// Commit message without subject and trailers, may be empty
+	pub body: String,
+	/// `Key: value` lines at the end of commit message
+	pub trailers: Vec<Trailer>,
+	/// Full commit message
 	pub message: String,
-	pub date: String,
 }
 
-impl AsyncIterator<io::Error> for GitLogParser {
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Signature {
+	pub name: String,
+	pub email: String,
+	pub time: GitTime,
+}
+
+impl Display for Signature {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} <{}>", self.name, self.email)
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Trailer {
+	pub key: String,
+	pub value: String,
+}
+
+impl Trailer {
+	/// Whether key is `Step-*` or commonly added by git and code review tools
+	fn is_known(&self) -> bool {
+		let key = self.key.as_bytes();
+		key.len() > 5 && key[..5].eq_ignore_ascii_case(b"Step-")
+			|| KNOWN_TRAILERS.iter().any(|it| it.eq_ignore_ascii_case(&self.key))
+	}
+}
+
+impl GitLog {
+	/// Value of first trailer named `key` (case insensitive) such as `Step-Title`
+	pub fn trailer(&self, key: &str) -> Option<&str> {
+		self.trailers.iter()
+			.find(|it| it.key.eq_ignore_ascii_case(key))
+			.map(|it| it.value.as_str())
+	}
+
+	pub fn is_merge(&self) -> bool {
+		self.parents.len() > 1
+	}
+}
+
+/// Trailer keys recognized without a body paragraph before them
+const KNOWN_TRAILERS: &[&str] = &[
+	"Signed-off-by",
+	"Co-authored-by",
+	"Reviewed-by",
+	"Acked-by",
+	"Tested-by",
+	"Reported-by",
+	"Suggested-by",
+	"Helped-by",
+	"Cc",
+	"Fixes",
+	"Closes",
+	"Refs",
+	"Change-Id",
+];
+
+/// Split commit message into subject, body and trailers
+pub(crate) fn split_message(message: &str) -> (String, String, Vec<Trailer>) {
+	let message = message.trim();
+	let (subject, body) = message.split_once('\n').unwrap_or((message, ""));
+	let body = body.trim();
+	// trailers are last paragraph of message and not a paragraph after subject
+	let (prose, last) = match body.rsplit_once("\n\n") {
+		Some((prose, last)) => (prose.trim_end(), last),
+		None => ("", body),
+	};
+	// a lone paragraph such as `Note: handle errors first` is prose unless every key is a known trailer
+	let trailers = parse_trailers(last).filter(|trailers| !prose.is_empty() || trailers.iter().all(Trailer::is_known));
+	match trailers {
+		Some(trailers) => (subject.trim().to_string(), prose.to_string(), trailers),
+		None => (subject.trim().to_string(), body.to_string(), Vec::new()),
+	}
+}
+
+/// Parse paragraph as trailers, `None` if any line is not a trailer
+fn parse_trailers(paragraph: &str) -> Option<Vec<Trailer>> {
+	let mut trailers: Vec<Trailer> = Vec::new();
+	for line in paragraph.lines() {
+		// folded value
+		if line.starts_with([' ', '\t']) {
+			let last = trailers.last_mut()?;
+			last.value.push(' ');
+			last.value.push_str(line.trim());
+			continue;
+		}
+		let (key, value) = line.split_once(':')?;
+		if key.is_empty() || !key.bytes().all(|it| it.is_ascii_alphanumeric() || it == b'-') {
+			return None;
+		}
+		trailers.push(Trailer {
+			key: key.to_string(),
+			value: value.trim().to_string(),
+		});
+	}
+	if trailers.is_empty() {
+		None
+	} else {
+		Some(trailers)
+	}
+}
+
+impl<R: AsyncRead + Unpin> AsyncIterator<StepdocsError> for GitLogParser<R> {
 	type Item = GitLog;
 
-	fn next<'a>(&'a mut self) -> Pin<Box<dyn Future<Output=Result<Option<Self::Item>, Error>> + 'a>> {
+	fn next<'a>(&'a mut self) -> Pin<Box<dyn Future<Output=Result<Option<Self::Item>>> + 'a>> {
 		Box::pin(self.next_log())
 	}
 }
 
 impl GitLogParser {
-	pub fn new(_child: Child, stdout: ChildStdout) -> Self {
+	pub fn new(child: Child, stdout: ChildStdout) -> Self {
+		Self {
+			child: Some(child),
+			inner: PeekableLine::new(BufReader::new(stdout)),
+		}
+	}
+}
+
+impl<R: AsyncRead + Unpin> GitLogParser<R> {
+	/// Parse log from any reader which produce output of [GitRepository::log](crate::git::GitRepository::log)
+	pub fn from_reader(reader: R) -> Self {
 		Self {
-			_child,
-			inner: BufReader::new(stdout),
+			child: None,
+			inner: PeekableLine::new(BufReader::new(reader)),
+		}
+	}
+
+	/// End of stream, report failure if `git` was failed
+	async fn finish<T>(&mut self) -> Result<Option<T>> {
+		if let Some(child) = &mut self.child {
+			check_exit(child, "log").await?;
 		}
+		Ok(None)
 	}
 
-	pub async fn next_log(&mut self) -> io::Result<Option<GitLog>> {
+	fn error(&self, kind: ParseErrorKind, text: impl Into<String>) -> ParseError {
+		ParseError::new(kind, text).at_line(self.inner.line())
+	}
+
+	pub async fn next_log(&mut self) -> Result<Option<GitLog>> {
 		let mut line = String::with_capacity(64);
 		while line.trim().is_empty() {
+			line.clear();
 			read_or_none!(self, line);
 		}
 
+		if line.len() < 40 || !line.bytes().all(|it| it.is_ascii_hexdigit()) {
+			return Err(self.error(ParseErrorKind::Log, line).into());
+		}
 		let hash = line;
 
-		let mut line = String::with_capacity(64);
-		read_or_none!(self, line);
-		let author = line;
+		let mut line = String::with_capacity(82);
+		read_or_eof!(self, line);
+		let parents = line.split_whitespace().map(str::to_string).collect();
 
-		let mut line = String::with_capacity(64);
-		read_or_none!(self, line);
-		let date = line;
+		let author = self.read_signature().await?;
+		let committer = self.read_signature().await?;
 
 		let mut message = String::with_capacity(128);
 		let mut line = String::with_capacity(64);
 		loop {
-			read_or_none!(self, line);
-			if line.as_str() == "==END==" {
+			read_or_eof!(self, line);
+			let end = line.find('\0');
+			message.push_str(&line[..end.unwrap_or(line.len())]);
+			if end.is_some() {
 				break;
 			}
-			message.push_str(&line);
+			message.push('\n');
 			line.clear();
 		}
+		message.truncate(message.trim_end().len());
+		let (subject, body, trailers) = split_message(&message);
 		Ok(
 			Some(GitLog {
 				hash,
+				parents,
 				author,
+				committer,
+				subject,
+				body,
+				trailers,
 				message,
-				date,
 			})
 		)
 	}
+
+	/// Read name, email and raw date lines
+	async fn read_signature(&mut self) -> Result<Signature> {
+		let mut name = String::with_capacity(32);
+		read_or_eof!(self, name);
+		let mut email = String::with_capacity(32);
+		read_or_eof!(self, email);
+		let mut date = String::with_capacity(24);
+		read_or_eof!(self, date);
+		let Some(time) = GitTime::parse_raw(&date) else {
+			return Err(self.error(ParseErrorKind::Log, date).into());
+		};
+		Ok(Signature { name, email, time })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::error::{ParseErrorKind, StepdocsError};
+	use crate::git::GitLogParser;
+	use crate::util::iter::AsyncIterator;
+
+	use super::split_message;
+
+	const LOG: &str = "f3ab7b7ef305cfa47f2cb6add43ec98f244950c9
+
+Wireless4024
+dev@example.com
+1697000000 +0700
+Committer
+committer@example.com
+1697003600 +0000
+Add parser
+
+Parse git log output.
+
+Step-Title: Parsing the log
+Co-authored-by: Someone <someone@example.com>
+\0
+0000000000000000000000000000000000000001
+f3ab7b7ef305cfa47f2cb6add43ec98f244950c9
+Wireless4024
+dev@example.com
+1697000000 +0700
+Wireless4024
+dev@example.com
+1697000000 +0700
+Second
+
+==END==
+Reviewed-by: is not a trailer
+when paragraph has prose
+\0";
+
+	#[tokio::test]
+	async fn test_parse_log() {
+		let logs = GitLogParser::from_reader(LOG.as_bytes()).collect().await.unwrap();
+		assert_eq!(logs.len(), 2);
+		let log = &logs[0];
+		assert!(log.parents.is_empty());
+		assert_eq!(log.author.name, "Wireless4024");
+		assert_eq!(log.author.email, "dev@example.com");
+		assert_eq!(log.author.time.offset, 420);
+		assert_eq!(log.committer.to_string(), "Committer <committer@example.com>");
+		assert_eq!(log.committer.time.seconds, 1697003600);
+		assert_eq!(log.subject, "Add parser");
+		assert_eq!(log.body, "Parse git log output.");
+		assert_eq!(log.trailer("step-title"), Some("Parsing the log"));
+		assert_eq!(log.trailer("Co-authored-by"), Some("Someone <someone@example.com>"));
+
+		let log = &logs[1];
+		assert_eq!(log.parents, ["f3ab7b7ef305cfa47f2cb6add43ec98f244950c9"]);
+		assert_eq!(log.subject, "Second");
+		assert_eq!(log.body, "==END==\nReviewed-by: is not a trailer\nwhen paragraph has prose");
+		assert!(log.trailers.is_empty());
+	}
+
+	#[test]
+	fn test_split_message() {
+		let (subject, body, trailers) = split_message("Add parser\n\nNote: handle errors first");
+		assert_eq!(subject, "Add parser");
+		assert_eq!(body, "Note: handle errors first");
+		assert!(trailers.is_empty());
+
+		let (_, body, trailers) = split_message("Add parser\n\nStep-Title: Parsing\nsigned-off-by: Someone");
+		assert_eq!(body, "");
+		assert_eq!(trailers.len(), 2);
+
+		let (_, body, trailers) = split_message("Add parser\n\nParse log.\n\nNote: handle errors first");
+		assert_eq!(body, "Parse log.");
+		assert_eq!(trailers[0].key, "Note");
+	}
+
+	#[tokio::test]
+	async fn test_truncated_log() {
+		let truncated = &LOG[..LOG.len() - 1];
+		let err = GitLogParser::from_reader(truncated.as_bytes()).collect().await.unwrap_err();
+		let StepdocsError::Parse(err) = err else { panic!("{err:?}") };
+		assert_eq!(err.kind, ParseErrorKind::UnexpectedEof);
+	}
 }
\ No newline at end of file
@@ -2,15 +2,27 @@ use std::process::exit;
 
 use bstr::{ByteSlice};
 
+pub use algorithm::{DiffAlgorithm, DiffOptions};
 pub use diff::*;
-pub use log_parser::GitLogParser;
-pub use repo::GitRepository;
+pub use log_options::{LogOptions, LogOrder};
+pub use log_parser::{GitLog, GitLogParser, LOG_FORMAT, Signature, Trailer};
+pub use time::GitTime;
+pub use word_diff::WordDiff;
+pub use repo::{GitBackend, GitDiffStream, GitLogStream, GitRepository, GitTag, TreeFile};
 
 use crate::util::proc::{RawOutputMessage, run_process};
 
+mod algorithm;
 mod repo;
+pub mod native;
+mod log_options;
 mod log_parser;
 mod diff;
+mod time;
+mod word_diff;
+mod slider;
+#[cfg(test)]
+pub(crate) mod fixture;
 
 pub async fn git_ver() -> Option<String> {
 	let output: RawOutputMessage = run_process("git", ["-v"], ".").await.into();
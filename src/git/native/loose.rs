@@ -0,0 +1,59 @@
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+
+use crate::error::{Result, StepdocsError};
+use crate::git::native::object::{Object, ObjectId, ObjectKind};
+
+/// Read zlib compressed object from `objects/xx/yyyy...`, `None` if it does not exist
+pub fn read_loose(objects: &Path, id: &ObjectId) -> Result<Option<Object>> {
+	let hex = id.to_hex();
+	let path = objects.join(&hex[..2]).join(&hex[2..]);
+	let compressed = match std::fs::read(&path) {
+		Ok(it) => it,
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+		Err(err) => return Err(err.into()),
+	};
+	let mut raw = Vec::new();
+	ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut raw)?;
+	parse_loose(id, raw).map(Some)
+}
+
+/// Split `<kind> <size>\0<content>` into object
+fn parse_loose(id: &ObjectId, mut raw: Vec<u8>) -> Result<Object> {
+	let corrupt = |reason: &str| StepdocsError::corrupt(format!("loose object {id}"), reason);
+	let nul = raw.iter().position(|it| *it == 0).ok_or_else(|| corrupt("missing header"))?;
+	let header = std::str::from_utf8(&raw[..nul]).map_err(|_| corrupt("invalid header"))?;
+	let (kind, size) = header.split_once(' ').ok_or_else(|| corrupt("invalid header"))?;
+	let kind = ObjectKind::parse(kind.as_bytes()).ok_or_else(|| corrupt("unknown object type"))?;
+	let size: usize = size.parse().map_err(|_| corrupt("invalid size"))?;
+	if raw.len() - nul - 1 != size {
+		return Err(corrupt("size does not match header"));
+	}
+	raw.drain(..=nul);
+	Ok(Object { kind, data: raw })
+}
+
+/// Find loose objects whose hex id start with `prefix`
+pub fn find_loose(objects: &Path, prefix: &str) -> Result<Vec<ObjectId>> {
+	if prefix.len() < 2 {
+		return Ok(vec![]);
+	}
+	let dir = match std::fs::read_dir(objects.join(&prefix[..2])) {
+		Ok(it) => it,
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+		Err(err) => return Err(err.into()),
+	};
+	let mut found = Vec::new();
+	for entry in dir {
+		let name = entry?.file_name();
+		let hex = format!("{}{}", &prefix[..2], name.to_string_lossy());
+		if hex.starts_with(prefix) {
+			if let Ok(id) = hex.parse() {
+				found.push(id);
+			}
+		}
+	}
+	Ok(found)
+}
@@ -0,0 +1,583 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+pub use object::{Commit, Object, ObjectId, ObjectKind, Tree, TreeEntry};
+
+use crate::error::{Result, StepdocsError};
+use crate::git::{GitLog, GitTag, LogOptions, LogOrder};
+use crate::git::log_parser::split_message;
+use crate::git::native::loose::{find_loose, read_loose};
+use crate::git::native::object::{parse_tag_message, parse_tag_target};
+use crate::git::native::pack::Pack;
+use crate::git::native::refs::Refs;
+
+mod loose;
+mod object;
+mod pack;
+mod refs;
+mod tree_diff;
+
+/// Minimum length of abbreviated object id
+const MIN_PREFIX: usize = 4;
+/// Annotated tags pointing to tags deeper than this are treated as a loop
+const MAX_PEEL_DEPTH: usize = 10;
+
+/// Read-only access to repository objects and refs without `git` binary
+#[derive(Debug)]
+pub struct NativeRepository {
+	git_dir: PathBuf,
+	objects: PathBuf,
+	refs: Refs,
+	packs: Vec<Pack>,
+}
+
+impl NativeRepository {
+	/// Open repository at work tree or `.git` directory `path`
+	pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+		let git_dir = find_git_dir(path.as_ref())?;
+		// linked work trees keep objects and refs in main repository
+		let common_dir = match std::fs::read_to_string(git_dir.join("commondir")) {
+			Ok(it) => git_dir.join(it.trim()),
+			Err(_) => git_dir.clone(),
+		};
+		let objects = common_dir.join("objects");
+		let mut packs = Vec::new();
+		if let Ok(dir) = std::fs::read_dir(objects.join("pack")) {
+			for entry in dir {
+				let path = entry?.path();
+				if path.extension().is_some_and(|it| it == "idx") {
+					packs.push(Pack::open(&path)?);
+				}
+			}
+		}
+		Ok(Self {
+			refs: Refs::new(&git_dir, &common_dir),
+			git_dir,
+			objects,
+			packs,
+		})
+	}
+
+	pub fn git_dir(&self) -> &Path {
+		&self.git_dir
+	}
+
+	/// Read raw object from loose objects or pack files
+	pub fn read_object(&self, id: &ObjectId) -> Result<Object> {
+		if let Some(object) = read_loose(&self.objects, id)? {
+			return Ok(object);
+		}
+		let resolve = |base: &ObjectId| self.read_object(base);
+		for pack in &self.packs {
+			if let Some(object) = pack.read(id, &resolve)? {
+				return Ok(object);
+			}
+		}
+		Err(StepdocsError::NotFound(id.to_hex()))
+	}
+
+	/// Read object of `kind` and its id, annotated tags are peeled
+	fn read_peeled(&self, id: &ObjectId, kind: ObjectKind) -> Result<(ObjectId, Object)> {
+		let mut id = *id;
+		for _ in 0..MAX_PEEL_DEPTH {
+			let object = self.read_object(&id)?;
+			if object.kind == kind {
+				return Ok((id, object));
+			}
+			if object.kind != ObjectKind::Tag {
+				break;
+			}
+			id = parse_tag_target(&object.data)
+				.ok_or_else(|| StepdocsError::corrupt(format!("tag {id}"), "missing object"))?;
+		}
+		Err(StepdocsError::corrupt(format!("object {id}"), format!("expect {kind}")))
+	}
+
+	pub fn read_commit(&self, id: &ObjectId) -> Result<Commit> {
+		let (id, object) = self.read_peeled(id, ObjectKind::Commit)?;
+		Commit::parse(id, &object.data)
+	}
+
+	/// Read tree object, commits are resolved to their root tree
+	pub fn read_tree(&self, id: &ObjectId) -> Result<Tree> {
+		let object = self.read_object(id)?;
+		match object.kind {
+			ObjectKind::Tree => Tree::parse(*id, &object.data),
+			_ => self.read_tree(&self.read_commit(id)?.tree),
+		}
+	}
+
+	pub fn read_blob(&self, id: &ObjectId) -> Result<Vec<u8>> {
+		Ok(self.read_peeled(id, ObjectKind::Blob)?.1.data)
+	}
+
+	/// Entry at `path` (separated by `/`) in tree or commit `root`
+	pub fn find_entry(&self, root: &ObjectId, path: &str) -> Result<Option<TreeEntry>> {
+		let mut tree = self.read_tree(root)?;
+		let mut parts = path.split('/').filter(|it| !it.is_empty()).peekable();
+		while let Some(name) = parts.next() {
+			let Some(entry) = tree.get(name) else {
+				return Ok(None);
+			};
+			if parts.peek().is_none() {
+				return Ok(Some(entry.clone()));
+			}
+			if !entry.is_tree() {
+				return Ok(None);
+			}
+			tree = self.read_tree(&entry.id)?;
+		}
+		Ok(None)
+	}
+
+	/// Every file in tree or commit `root` with its full path, like `git ls-tree -r`
+	pub fn ls_tree(&self, root: &ObjectId) -> Result<Vec<(String, TreeEntry)>> {
+		let mut files = Vec::new();
+		let mut pending = vec![(String::new(), *root)];
+		while let Some((prefix, id)) = pending.pop() {
+			for entry in self.read_tree(&id)?.entries {
+				let path = format!("{prefix}{}", entry.name);
+				if entry.is_tree() {
+					pending.push((format!("{path}/"), entry.id));
+				} else {
+					files.push((path, entry));
+				}
+			}
+		}
+		files.sort_by(|a, b| a.0.cmp(&b.0));
+		Ok(files)
+	}
+
+	/// Every ref under `refs/` with the object it points to
+	pub fn refs(&self) -> Result<Vec<(String, ObjectId)>> {
+		self.refs.all()
+	}
+
+	/// Every tag under `refs/tags/` pointing to a commit, tags of other objects are left out
+	pub fn tags(&self) -> Result<Vec<GitTag>> {
+		Ok(self.refs()?.into_iter()
+			.filter_map(|(name, id)| {
+				let name = name.strip_prefix("refs/tags/")?.to_string();
+				let commit = self.read_commit(&id).ok()?;
+				// message of annotated tag, lightweight tag point to commit directly
+				let message = self.read_object(&id).ok()
+					.filter(|it| it.kind == ObjectKind::Tag)
+					.map(|it| parse_tag_message(&it.data));
+				Some(GitTag { name, commit: commit.id.to_hex(), message })
+			})
+			.collect())
+	}
+
+	/// Resolve object id, abbreviated id or ref name with optional `~n`, `^n` and `^{}` or `^{commit}` suffixes to commit
+	pub fn resolve(&self, rev: &str) -> Result<ObjectId> {
+		let not_found = || StepdocsError::NotFound(rev.to_string());
+		let split = rev.find(['~', '^']).unwrap_or(rev.len());
+		let (name, mut suffix) = rev.split_at(split);
+		let mut id = self.resolve_name(name)?.ok_or_else(not_found)?;
+		if !suffix.is_empty() {
+			id = self.read_commit(&id)?.id;
+		}
+		while let Some(op) = suffix.chars().next() {
+			suffix = &suffix[op.len_utf8()..];
+			if op == '^' && suffix.starts_with('{') {
+				// resolved object is already peeled to commit
+				let end = suffix.find('}').ok_or_else(not_found)?;
+				if !matches!(&suffix[1..end], "" | "commit") {
+					return Err(StepdocsError::NotFound(format!("{rev} (only commits can be walked)")));
+				}
+				suffix = &suffix[end + 1..];
+				continue;
+			}
+			if op != '~' && op != '^' {
+				return Err(not_found());
+			}
+			let digits = suffix.find(|it: char| !it.is_ascii_digit()).unwrap_or(suffix.len());
+			let n = match &suffix[..digits] {
+				"" => 1,
+				n => n.parse::<usize>().map_err(|_| not_found())?,
+			};
+			suffix = &suffix[digits..];
+			if op == '^' {
+				if n > 0 {
+					id = *self.read_commit(&id)?.parents.get(n - 1).ok_or_else(not_found)?;
+				}
+				continue;
+			}
+			for _ in 0..n {
+				id = *self.read_commit(&id)?.parents.first().ok_or_else(not_found)?;
+			}
+		}
+		Ok(id)
+	}
+
+	fn resolve_name(&self, name: &str) -> Result<Option<ObjectId>> {
+		if name.len() == 40 {
+			if let Ok(id) = name.parse() {
+				return Ok(Some(id));
+			}
+		}
+		let name = if name.is_empty() { "HEAD" } else { name };
+		for candidate in [
+			name.to_string(),
+			format!("refs/{name}"),
+			format!("refs/tags/{name}"),
+			format!("refs/heads/{name}"),
+			format!("refs/remotes/{name}"),
+			format!("refs/remotes/{name}/HEAD"),
+		] {
+			if let Some(id) = self.refs.resolve(&candidate)? {
+				return Ok(Some(id));
+			}
+		}
+		self.find_prefix(name)
+	}
+
+	/// Find unique object whose hex id start with `prefix`
+	fn find_prefix(&self, prefix: &str) -> Result<Option<ObjectId>> {
+		let prefix = prefix.to_ascii_lowercase();
+		if prefix.len() < MIN_PREFIX || !prefix.bytes().all(|it| it.is_ascii_hexdigit()) {
+			return Ok(None);
+		}
+		let mut found = find_loose(&self.objects, &prefix)?;
+		for pack in &self.packs {
+			found.extend(pack.index().find_prefix(&prefix));
+		}
+		found.sort();
+		found.dedup();
+		match found.as_slice() {
+			[id] => Ok(Some(*id)),
+			[] => Ok(None),
+			_ => Err(StepdocsError::NotFound(format!("{prefix} (ambiguous)"))),
+		}
+	}
+
+	/// Commits selected by `options`, ordered like `git log` with the same options.
+	/// Path limiting hides commits unchanged against a parent and only walks that parent,
+	/// like default history simplification of git
+	pub fn log(&self, options: &LogOptions) -> Result<Vec<GitLog>> {
+		let (tips, exclude) = self.revisions(options)?;
+		let mut seen = HashSet::new();
+		for id in exclude {
+			self.ancestors(id, &mut seen)?;
+		}
+		// newest commit first by committer date, earlier queued commit first on tie
+		let mut queue = BinaryHeap::new();
+		let mut counter = 0;
+		let mut commits = Vec::new();
+		// walked parent of every commit hidden by path limiting
+		let mut hidden = HashMap::new();
+		for id in tips {
+			let commit = self.read_commit(&id)?;
+			if seen.insert(commit.id) {
+				queue.push((commit.committer.time.seconds, Reverse(counter), QueuedCommit(commit)));
+				counter += 1;
+			}
+		}
+		while let Some((_, _, QueuedCommit(commit))) = queue.pop() {
+			let (parents, shown) = self.walked_parents(&commit, options)?;
+			for parent in &parents {
+				if seen.insert(*parent) {
+					let parent = self.read_commit(parent)?;
+					queue.push((parent.committer.time.seconds, Reverse(counter), QueuedCommit(parent)));
+					counter += 1;
+				}
+			}
+			if shown {
+				commits.push(commit);
+			} else {
+				hidden.insert(commit.id, parents.first().copied());
+			}
+		}
+		if options.order == LogOrder::Topo {
+			// parents are rewritten to nearest listed ancestor like git does
+			let rewrite = |mut id: ObjectId| {
+				while let Some(next) = hidden.get(&id) {
+					id = (*next)?;
+				}
+				Some(id)
+			};
+			let parents = commits.iter()
+				.map(|commit| {
+					let mut found = HashSet::new();
+					commit.parents.iter()
+						.filter_map(|it| rewrite(*it))
+						.filter(|it| found.insert(*it))
+						.collect()
+				})
+				.collect();
+			commits = topo_order(commits, parents);
+		}
+		if options.reverse {
+			commits.reverse();
+		}
+		Ok(commits.into_iter().map(GitLog::from).collect())
+	}
+
+	/// Commits to walk from and commits whose ancestors are left out for revisions of `options`,
+	/// `A..B` is `B ^A` and `A...B` is `A B` without common ancestors, missing side is `HEAD`
+	fn revisions(&self, options: &LogOptions) -> Result<(Vec<ObjectId>, Vec<ObjectId>)> {
+		let (mut tips, mut exclude) = (Vec::new(), Vec::new());
+		let head = |rev: &str| if rev.is_empty() { "HEAD".to_string() } else { rev.to_string() };
+		for rev in options.revisions()? {
+			if rev == "--all" {
+				// refs may also point to trees or blobs
+				tips.extend(self.refs()?.into_iter().filter_map(|(_, id)| self.read_commit(&id).ok().map(|it| it.id)));
+				if let Ok(head) = self.resolve("HEAD") {
+					tips.push(head);
+				}
+			} else if let Some(rev) = rev.strip_prefix('^') {
+				exclude.push(self.resolve(rev)?);
+			} else if let Some((from, to)) = rev.split_once("...") {
+				let (from, to) = (self.resolve(&head(from))?, self.resolve(&head(to))?);
+				let mut common = HashSet::new();
+				self.ancestors(from, &mut common)?;
+				let mut reachable = HashSet::new();
+				self.ancestors(to, &mut reachable)?;
+				exclude.extend(common.intersection(&reachable));
+				tips.extend([from, to]);
+			} else if let Some((from, to)) = rev.split_once("..") {
+				tips.push(self.resolve(&head(to))?);
+				exclude.push(self.resolve(&head(from))?);
+			} else {
+				tips.push(self.resolve(rev)?);
+			}
+		}
+		Ok((tips, exclude))
+	}
+
+	/// Parents walked after `commit` and whether it is listed,
+	/// with path limiting a commit unchanged against any parent is hidden and only that parent is walked
+	fn walked_parents(&self, commit: &Commit, options: &LogOptions) -> Result<(Vec<ObjectId>, bool)> {
+		let parents = match options.first_parent {
+			true => &commit.parents[..commit.parents.len().min(1)],
+			false => &commit.parents[..],
+		};
+		if options.paths.is_empty() {
+			return Ok((parents.to_vec(), true));
+		}
+		let entries = self.path_entries(&commit.tree, &options.paths)?;
+		if parents.is_empty() {
+			return Ok((vec![], entries.iter().any(Option::is_some)));
+		}
+		for parent in parents {
+			let tree = self.read_commit(parent)?.tree;
+			if self.path_entries(&tree, &options.paths)? == entries {
+				return Ok((vec![*parent], false));
+			}
+		}
+		Ok((parents.to_vec(), true))
+	}
+
+	/// Mode and object at every path in `tree`, `None` for missing path
+	fn path_entries(&self, tree: &ObjectId, paths: &[String]) -> Result<Vec<Option<(u32, ObjectId)>>> {
+		paths.iter()
+			.map(|path| match path.split('/').all(|it| it.is_empty() || it == ".") {
+				true => Ok(Some((0o40000, *tree))),
+				false => Ok(self.find_entry(tree, path)?.map(|it| (it.mode, it.id))),
+			})
+			.collect()
+	}
+
+	/// Mark `id` and every ancestor as seen
+	fn ancestors(&self, id: ObjectId, seen: &mut HashSet<ObjectId>) -> Result<()> {
+		let mut stack = vec![id];
+		while let Some(id) = stack.pop() {
+			if seen.contains(&id) {
+				continue;
+			}
+			let commit = self.read_commit(&id)?;
+			seen.insert(id);
+			seen.insert(commit.id);
+			stack.extend(commit.parents);
+		}
+		Ok(())
+	}
+}
+
+/// Order commits so no parent comes before its children and lines of history are not intermixed,
+/// second parent of a merge is shown before first parent like `git log --topo-order`,
+/// `parents` of every commit are used for ordering
+fn topo_order(commits: Vec<Commit>, parents: Vec<Vec<ObjectId>>) -> Vec<Commit> {
+	let index: HashMap<_, _> = commits.iter().enumerate().map(|(at, it)| (it.id, at)).collect();
+	let parents = |at: usize| parents[at].iter().filter_map(|it| index.get(it).copied()).collect::<Vec<_>>();
+	let mut children = vec![0; commits.len()];
+	for at in 0..commits.len() {
+		for parent in parents(at) {
+			children[parent] += 1;
+		}
+	}
+	// tips are taken in walk order, parents of taken commit are stacked in order
+	let mut stack: Vec<_> = (0..commits.len()).rev().filter(|it| children[*it] == 0).collect();
+	let mut order = Vec::with_capacity(commits.len());
+	while let Some(at) = stack.pop() {
+		for parent in parents(at) {
+			children[parent] -= 1;
+			if children[parent] == 0 {
+				stack.push(parent);
+			}
+		}
+		order.push(at);
+	}
+	let mut commits: Vec<_> = commits.into_iter().map(Some).collect();
+	order.into_iter().filter_map(|at| commits[at].take()).collect()
+}
+
+/// Commit in walk queue, ordering is decided by the fields before it
+struct QueuedCommit(Commit);
+
+impl PartialEq for QueuedCommit {
+	fn eq(&self, _: &Self) -> bool {
+		true
+	}
+}
+
+impl Eq for QueuedCommit {}
+
+impl PartialOrd for QueuedCommit {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for QueuedCommit {
+	fn cmp(&self, _: &Self) -> std::cmp::Ordering {
+		std::cmp::Ordering::Equal
+	}
+}
+
+impl From<Commit> for GitLog {
+	fn from(commit: Commit) -> Self {
+		let message = commit.message.trim_end().to_string();
+		let (subject, body, trailers) = split_message(&message);
+		GitLog {
+			hash: commit.id.to_hex(),
+			parents: commit.parents.iter().map(ObjectId::to_hex).collect(),
+			author: commit.author,
+			committer: commit.committer,
+			subject,
+			body,
+			trailers,
+			message,
+		}
+	}
+}
+
+/// Locate `.git` directory of work tree, `path` itself may be a bare repository
+fn find_git_dir(path: &Path) -> Result<PathBuf> {
+	let dot_git = path.join(".git");
+	if dot_git.is_dir() {
+		return Ok(dot_git);
+	}
+	// linked work tree or submodule: `gitdir: <path>`
+	if dot_git.is_file() {
+		let content = std::fs::read_to_string(&dot_git)?;
+		let target = content.trim().strip_prefix("gitdir:")
+			.ok_or_else(|| StepdocsError::corrupt(dot_git.display().to_string(), "expect `gitdir:`"))?;
+		return Ok(path.join(target.trim()));
+	}
+	if path.join("objects").is_dir() && path.join("HEAD").is_file() {
+		return Ok(path.to_path_buf());
+	}
+	Err(StepdocsError::NotFound(format!("git repository at {}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::git::fixture::TestRepo;
+	use crate::git::native::{topo_order, Commit, NativeRepository, ObjectId};
+	use crate::git::{GitBackend, GitRepository, LogOptions, LogOrder, Signature};
+	use crate::util::iter::AsyncIterator;
+
+	fn commit(no: u8, parents: &[u8]) -> Commit {
+		Commit {
+			id: ObjectId([no; 20]),
+			tree: ObjectId::default(),
+			parents: parents.iter().map(|it| ObjectId([*it; 20])).collect(),
+			author: Signature::default(),
+			committer: Signature::default(),
+			message: String::new(),
+		}
+	}
+
+	#[test]
+	fn test_topo_order() {
+		// 4 merges 3 into 2, both branch from 1, walked by date as 4 2 1 3
+		let commits = vec![commit(4, &[2, 3]), commit(2, &[1]), commit(1, &[]), commit(3, &[1])];
+		let parents = commits.iter().map(|it| it.parents.clone()).collect();
+		let order = topo_order(commits, parents).iter().map(|it| it.id.0[0]).collect::<Vec<_>>();
+		assert_eq!(order, [4, 3, 2, 1]);
+
+		// parent rewritten to 1 keeps 2 after 3
+		let commits = vec![commit(3, &[9]), commit(2, &[1]), commit(1, &[])];
+		let parents = vec![vec![ObjectId([1; 20])], vec![ObjectId([1; 20])], vec![]];
+		let order = topo_order(commits, parents).iter().map(|it| it.id.0[0]).collect::<Vec<_>>();
+		assert_eq!(order, [3, 2, 1]);
+	}
+
+	#[test]
+	fn test_resolve_ref() {
+		let test = TestRepo::new("native-resolve-ref");
+		let first = test.commit("first");
+		test.git(&["branch", "config"]);
+		test.git(&["tag", "same"]);
+		let second = test.commit("second");
+		test.git(&["branch", "same"]);
+		test.git(&["reset", "-q", "--hard", "HEAD~1"]);
+
+		let repo = NativeRepository::open(test.path()).unwrap();
+		// branch named like a file in git directory
+		assert_eq!(repo.resolve("config").unwrap().to_string(), first);
+		// tag before branch of same name
+		assert_eq!(repo.resolve("same").unwrap().to_string(), first);
+		assert_eq!(repo.resolve("heads/same").unwrap().to_string(), second);
+		assert_eq!(repo.resolve("ORIG_HEAD").unwrap().to_string(), second);
+		assert!(repo.resolve("description").is_err());
+		assert!(repo.resolve("refs/../HEAD").is_err());
+	}
+
+	#[tokio::test]
+	async fn test_log() {
+		let test = TestRepo::new("native-log");
+		test.write("a.txt", "1\n");
+		test.write("b.txt", "1\n");
+		test.commit("Root");
+		test.git(&["checkout", "-q", "-b", "side"]);
+		test.write("a.txt", "2\n");
+		test.commit("Change a");
+		test.write("c.txt", "1\n");
+		test.commit("Add c");
+		test.git(&["checkout", "-q", "main"]);
+		test.write("b.txt", "2\n");
+		test.commit("Change b");
+		test.git(&["merge", "-q", "--no-ff", "side", "-m", "Merge side"]);
+		test.write("a.txt", "3\n");
+		test.commit("Change a again");
+
+		let native = NativeRepository::open(test.path()).unwrap();
+		let process = GitRepository::open(test.path(), GitBackend::Process).unwrap();
+		let teaching = LogOptions::new().order(LogOrder::Topo).reverse(true);
+		for (options, expected) in [
+			(teaching.clone(), &["Root", "Change b", "Change a", "Add c", "Merge side", "Change a again"][..]),
+			(teaching.clone().first_parent(true).range("main"), &["Root", "Change b", "Merge side", "Change a again"]),
+			// merge is hidden as it is unchanged against side for `a.txt` and against main for `b.txt`
+			(teaching.clone().path("a.txt"), &["Root", "Change a", "Change a again"]),
+			(teaching.clone().path("b.txt"), &["Root", "Change b"]),
+			(teaching.clone().path("c.txt").path("missing"), &["Add c"]),
+			(teaching.clone().range("side...main"), &["Change b", "Merge side", "Change a again"]),
+			(teaching.clone().range("main~2..main"), &["Change a", "Add c", "Merge side", "Change a again"]),
+		] {
+			let subjects = native.log(&options).unwrap().into_iter().map(|it| it.subject).collect::<Vec<_>>();
+			assert_eq!(subjects, expected, "{options:?}");
+			let logs = process.log(&options).unwrap().collect().await.unwrap();
+			assert_eq!(logs.into_iter().map(|it| it.subject).collect::<Vec<_>>(), expected, "git {options:?}");
+		}
+
+		// range from config must not inject options into `git log`
+		let output = test.path().join("pwned");
+		let options = LogOptions::new().range(format!("--output={}", output.display()));
+		assert!(native.log(&options).is_err());
+		assert!(process.log(&options).is_err());
+		assert!(!output.exists());
+	}
+}
@@ -0,0 +1,290 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+use crate::error::{Result, StepdocsError};
+use crate::git::{GitTime, Signature};
+
+/// SHA-1 object id
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Default)]
+pub struct ObjectId(pub [u8; 20]);
+
+impl ObjectId {
+	pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+		Some(Self(bytes.try_into().ok()?))
+	}
+
+	pub fn to_hex(&self) -> String {
+		self.to_string()
+	}
+}
+
+impl FromStr for ObjectId {
+	type Err = StepdocsError;
+
+	fn from_str(hex: &str) -> Result<Self> {
+		let hex = hex.trim();
+		// non-ASCII name could split a character when sliced by byte
+		if hex.len() != 40 || !hex.is_ascii() {
+			return Err(StepdocsError::NotFound(hex.to_string()));
+		}
+		let mut id = [0u8; 20];
+		for (i, byte) in id.iter_mut().enumerate() {
+			*byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+				.map_err(|_| StepdocsError::NotFound(hex.to_string()))?;
+		}
+		Ok(Self(id))
+	}
+}
+
+impl Display for ObjectId {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		for byte in self.0 {
+			write!(f, "{byte:02x}")?;
+		}
+		Ok(())
+	}
+}
+
+impl Debug for ObjectId {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "ObjectId({self})")
+	}
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ObjectKind {
+	Commit,
+	Tree,
+	Blob,
+	Tag,
+}
+
+impl ObjectKind {
+	/// Kind from type number used in pack file
+	pub fn from_pack_type(typ: u8) -> Option<Self> {
+		Some(match typ {
+			1 => ObjectKind::Commit,
+			2 => ObjectKind::Tree,
+			3 => ObjectKind::Blob,
+			4 => ObjectKind::Tag,
+			_ => return None,
+		})
+	}
+
+	pub fn parse(name: &[u8]) -> Option<Self> {
+		Some(match name {
+			b"commit" => ObjectKind::Commit,
+			b"tree" => ObjectKind::Tree,
+			b"blob" => ObjectKind::Blob,
+			b"tag" => ObjectKind::Tag,
+			_ => return None,
+		})
+	}
+}
+
+impl Display for ObjectKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			ObjectKind::Commit => "commit",
+			ObjectKind::Tree => "tree",
+			ObjectKind::Blob => "blob",
+			ObjectKind::Tag => "tag",
+		})
+	}
+}
+
+/// Raw content of git object
+#[derive(Debug, Clone)]
+pub struct Object {
+	pub kind: ObjectKind,
+	pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Commit {
+	pub id: ObjectId,
+	pub tree: ObjectId,
+	pub parents: Vec<ObjectId>,
+	pub author: Signature,
+	pub committer: Signature,
+	pub message: String,
+}
+
+impl Commit {
+	pub fn parse(id: ObjectId, data: &[u8]) -> Result<Self> {
+		let corrupt = |reason: &str| StepdocsError::corrupt(format!("commit {id}"), reason);
+		let text = String::from_utf8_lossy(data);
+		let (header, message) = text.split_once("\n\n").unwrap_or((&text, ""));
+		let mut tree = None;
+		let mut parents = Vec::new();
+		let mut author = None;
+		let mut committer = None;
+		for line in header.lines() {
+			// continuation of multi-line header such as `gpgsig`
+			if line.starts_with(' ') {
+				continue;
+			}
+			let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+			match key {
+				"tree" => tree = Some(value.parse()?),
+				"parent" => parents.push(value.parse()?),
+				"author" => author = parse_signature(value),
+				"committer" => committer = parse_signature(value),
+				_ => {}
+			}
+		}
+		Ok(Self {
+			id,
+			tree: tree.ok_or_else(|| corrupt("missing tree"))?,
+			parents,
+			author: author.ok_or_else(|| corrupt("missing author"))?,
+			committer: committer.ok_or_else(|| corrupt("missing committer"))?,
+			message: message.to_string(),
+		})
+	}
+}
+
+/// Parse `Name <email> 1697000000 +0700`
+pub fn parse_signature(value: &str) -> Option<Signature> {
+	let (ident, time) = value.rsplit_once('>')?;
+	let (name, email) = ident.split_once('<')?;
+	Some(Signature {
+		name: name.trim().to_string(),
+		email: email.trim().to_string(),
+		time: GitTime::parse_raw(time)?,
+	})
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TreeEntry {
+	pub mode: u32,
+	pub name: String,
+	pub id: ObjectId,
+}
+
+impl TreeEntry {
+	pub fn is_tree(&self) -> bool {
+		self.mode == 0o40000
+	}
+
+	/// Gitlink entry (submodule) point to commit in another repository
+	pub fn is_submodule(&self) -> bool {
+		self.mode == 0o160000
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+	pub entries: Vec<TreeEntry>,
+}
+
+impl Tree {
+	pub fn parse(id: ObjectId, mut data: &[u8]) -> Result<Self> {
+		let corrupt = || StepdocsError::corrupt(format!("tree {id}"), "truncated entry");
+		let mut entries = Vec::new();
+		while !data.is_empty() {
+			let space = data.iter().position(|it| *it == b' ').ok_or_else(corrupt)?;
+			let nul = data.iter().position(|it| *it == 0).ok_or_else(corrupt)?;
+			if nul < space || data.len() < nul + 21 {
+				return Err(corrupt());
+			}
+			let mode = std::str::from_utf8(&data[..space]).ok()
+				.and_then(|it| u32::from_str_radix(it, 8).ok())
+				.ok_or_else(corrupt)?;
+			entries.push(TreeEntry {
+				mode,
+				name: String::from_utf8_lossy(&data[space + 1..nul]).to_string(),
+				id: ObjectId::from_bytes(&data[nul + 1..nul + 21]).ok_or_else(corrupt)?,
+			});
+			data = &data[nul + 21..];
+		}
+		Ok(Self { entries })
+	}
+
+	pub fn get(&self, name: &str) -> Option<&TreeEntry> {
+		self.entries.iter().find(|it| it.name == name)
+	}
+}
+
+/// Message of annotated tag object without its PGP, SSH or X.509 signature
+pub fn parse_tag_message(data: &[u8]) -> String {
+	let text = String::from_utf8_lossy(data);
+	let message = text.split_once("\n\n").map(|it| it.1).unwrap_or_default();
+	// signature starts at a line such as `-----BEGIN SSH SIGNATURE-----`
+	let end = message.match_indices("-----BEGIN ")
+		.map(|(at, _)| at)
+		.find(|&at| at == 0 || message[..at].ends_with('\n'))
+		.unwrap_or(message.len());
+	message[..end].trim().to_string()
+}
+
+/// Return target of annotated tag object
+pub fn parse_tag_target(data: &[u8]) -> Option<ObjectId> {
+	let text = std::str::from_utf8(data).ok()?;
+	text.lines()
+		.find_map(|it| it.strip_prefix("object "))
+		.and_then(|it| it.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::git::native::object::{parse_tag_message, Commit, ObjectId, Tree};
+
+	const COMMIT: &str = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904
+parent 0123456789abcdef0123456789abcdef01234567
+author Jane Doe <jane@example.com> 1697000000 +0700
+committer John Doe <john@example.com> 1697000100 -0130
+gpgsig -----BEGIN PGP SIGNATURE-----
+ abc
+ -----END PGP SIGNATURE-----
+
+Add parser
+
+Step-Title: Parsing
+";
+
+	#[test]
+	fn test_parse_commit() {
+		let commit = Commit::parse(ObjectId::default(), COMMIT.as_bytes()).unwrap();
+		assert_eq!(commit.tree.to_hex(), "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+		assert_eq!(commit.parents.len(), 1);
+		assert_eq!(commit.author.to_string(), "Jane Doe <jane@example.com>");
+		assert_eq!(commit.committer.time.offset, -90);
+		assert_eq!(commit.message, "Add parser\n\nStep-Title: Parsing\n");
+
+		assert!(Commit::parse(ObjectId::default(), b"parent abc\n\nmessage").is_err());
+	}
+
+	#[test]
+	fn test_parse_id() {
+		let id: ObjectId = "4b825dc642cb6eb9a060e54bf8d69288fbee4904".parse().unwrap();
+		assert_eq!(id.to_hex(), "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+		assert!("aéééééééééééééaaaaaaaaaaaaa".parse::<ObjectId>().is_err());
+		assert!("4b825dc642cb6eb9a060e54bf8d69288fbee490g".parse::<ObjectId>().is_err());
+	}
+
+	#[test]
+	fn test_parse_tag_message() {
+		let tag = "object 4b825dc642cb6eb9a060e54bf8d69288fbee4904\ntype commit\ntag v1\ntagger Jane Doe <jane@example.com> 1697000000 +0700\n\n";
+		for signature in ["PGP SIGNATURE", "SSH SIGNATURE", "SIGNED MESSAGE"] {
+			let data = format!("{tag}Chapter one\n\nUse ----- as rule\n-----BEGIN {signature}-----\nabc\n-----END {signature}-----\n");
+			assert_eq!(parse_tag_message(data.as_bytes()), "Chapter one\n\nUse ----- as rule");
+		}
+		assert_eq!(parse_tag_message(tag.as_bytes()), "");
+	}
+
+	#[test]
+	fn test_parse_tree() {
+		let mut data = b"100644 main.rs\0".to_vec();
+		data.extend([0xab; 20]);
+		data.extend(b"40000 src\0");
+		data.extend([0x01; 20]);
+		let tree = Tree::parse(ObjectId::default(), &data).unwrap();
+		assert_eq!(tree.entries.len(), 2);
+		assert_eq!(tree.entries[0].mode, 0o100644);
+		assert_eq!(tree.get("main.rs").unwrap().id.to_hex(), "ab".repeat(20));
+		assert!(tree.get("src").unwrap().is_tree());
+
+		assert!(Tree::parse(ObjectId::default(), &data[..30]).is_err());
+	}
+}
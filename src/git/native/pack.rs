@@ -0,0 +1,377 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use flate2::read::ZlibDecoder;
+
+use crate::error::{Result, StepdocsError};
+use crate::git::native::object::{Object, ObjectId, ObjectKind};
+
+const IDX_MAGIC: &[u8] = b"\xfftOc";
+const PACK_MAGIC: &[u8] = b"PACK";
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+/// Longest delta chain followed, same as maximum `--depth` of `git pack-objects`
+const MAX_DELTA_DEPTH: usize = 4095;
+
+/// Content of version 2 `.idx` file
+#[derive(Debug)]
+pub struct PackIndex {
+	fanout: [u32; 256],
+	ids: Vec<ObjectId>,
+	offsets: Vec<u64>,
+}
+
+impl PackIndex {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let corrupt = |reason: &str| StepdocsError::corrupt("pack index", reason);
+		if data.len() < 8 + 256 * 4 || &data[..4] != IDX_MAGIC {
+			return Err(corrupt("unsupported index version"));
+		}
+		if be_u32(&data[4..]) != 2 {
+			return Err(corrupt("unsupported index version"));
+		}
+		let mut fanout = [0u32; 256];
+		for (i, it) in fanout.iter_mut().enumerate() {
+			*it = be_u32(&data[8 + i * 4..]);
+		}
+		let count = fanout[255] as usize;
+		let ids_at = 8 + 256 * 4;
+		let offsets_at = ids_at + count * 20 + count * 4;
+		let large_at = offsets_at + count * 4;
+		if data.len() < large_at {
+			return Err(corrupt("truncated index"));
+		}
+		let ids = data[ids_at..ids_at + count * 20]
+			.chunks_exact(20)
+			.map(|it| ObjectId::from_bytes(it).unwrap())
+			.collect();
+		let mut offsets = Vec::with_capacity(count);
+		for i in 0..count {
+			let offset = be_u32(&data[offsets_at + i * 4..]);
+			if offset & 0x8000_0000 == 0 {
+				offsets.push(offset as u64);
+				continue;
+			}
+			// index into table of 64 bit offsets for packs larger than 2 GiB
+			let at = large_at + (offset & 0x7fff_ffff) as usize * 8;
+			let large = data.get(at..at + 8).ok_or_else(|| corrupt("truncated large offset"))?;
+			offsets.push(u64::from_be_bytes(large.try_into().unwrap()));
+		}
+		Ok(Self { fanout, ids, offsets })
+	}
+
+	/// Indices of objects whose id start with `first` byte
+	fn bucket(&self, first: u8) -> std::ops::Range<usize> {
+		let start = if first == 0 { 0 } else { self.fanout[first as usize - 1] as usize };
+		start..self.fanout[first as usize] as usize
+	}
+
+	/// Offset of object `id` in pack file
+	pub fn find(&self, id: &ObjectId) -> Option<u64> {
+		let range = self.bucket(id.0[0]);
+		let idx = self.ids[range.clone()].binary_search(id).ok()?;
+		Some(self.offsets[range.start + idx])
+	}
+
+	/// Objects whose hex id start with `prefix`
+	pub fn find_prefix(&self, prefix: &str) -> Vec<ObjectId> {
+		let Some(first) = prefix.get(..2).and_then(|it| u8::from_str_radix(it, 16).ok()) else {
+			return vec![];
+		};
+		self.ids[self.bucket(first)].iter()
+			.filter(|it| it.to_hex().starts_with(prefix))
+			.copied()
+			.collect()
+	}
+}
+
+/// Pack file with its index, content is read on first access
+#[derive(Debug)]
+pub struct Pack {
+	path: PathBuf,
+	index: PackIndex,
+	data: OnceLock<Vec<u8>>,
+}
+
+impl Pack {
+	/// Open pack from path of its `.idx` file
+	pub fn open(idx: &Path) -> Result<Self> {
+		let index = PackIndex::parse(&std::fs::read(idx)?)?;
+		Ok(Self {
+			path: idx.with_extension("pack"),
+			index,
+			data: OnceLock::new(),
+		})
+	}
+
+	pub fn index(&self) -> &PackIndex {
+		&self.index
+	}
+
+	fn data(&self) -> Result<&[u8]> {
+		if let Some(data) = self.data.get() {
+			return Ok(data);
+		}
+		let data = std::fs::read(&self.path)?;
+		if data.len() < 12 || &data[..4] != PACK_MAGIC {
+			return Err(StepdocsError::corrupt(self.path.display().to_string(), "invalid pack header"));
+		}
+		Ok(self.data.get_or_init(|| data))
+	}
+
+	/// Read object `id` if it is in this pack, `resolve` is used for bases of `REF_DELTA` outside of this pack
+	pub fn read(&self, id: &ObjectId, resolve: &dyn Fn(&ObjectId) -> Result<Object>) -> Result<Option<Object>> {
+		match self.index.find(id) {
+			Some(offset) => self.read_at(offset, resolve).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	fn read_at(&self, offset: u64, resolve: &dyn Fn(&ObjectId) -> Result<Object>) -> Result<Object> {
+		// deltas from requested object down to its base, applied in reverse
+		let mut deltas = Vec::new();
+		let mut offset = offset;
+		let mut base = loop {
+			let entry = self.entry_at(offset)?;
+			let next = match entry.base {
+				DeltaBase::None => break Object {
+					kind: ObjectKind::from_pack_type(entry.typ).ok_or_else(|| self.corrupt(offset, "unknown object type"))?,
+					data: self.inflate(&entry)?,
+				},
+				DeltaBase::Offset(base) => Ok(base),
+				DeltaBase::Id(id) => self.index.find(&id).ok_or(id),
+			};
+			if deltas.len() >= MAX_DELTA_DEPTH {
+				return Err(self.corrupt(offset, "delta chain too deep"));
+			}
+			deltas.push(entry);
+			match next {
+				Ok(base) => offset = base,
+				// base outside of this pack
+				Err(id) => break resolve(&id)?,
+			}
+		};
+		for entry in deltas.iter().rev() {
+			let delta = self.inflate(entry)?;
+			base.data = apply_delta(&base.data, &delta).ok_or_else(|| self.corrupt(entry.offset, "invalid delta"))?;
+		}
+		Ok(base)
+	}
+
+	fn corrupt(&self, offset: u64, reason: &str) -> StepdocsError {
+		StepdocsError::corrupt(format!("{} at {offset}", self.path.display()), reason)
+	}
+
+	/// Parse header of entry at `offset`
+	fn entry_at(&self, offset: u64) -> Result<Entry> {
+		let data = self.data()?;
+		let corrupt = |reason: &str| self.corrupt(offset, reason);
+		let mut pos = usize::try_from(offset).map_err(|_| corrupt("offset out of range"))?;
+		let mut byte = *data.get(pos).ok_or_else(|| corrupt("offset out of range"))?;
+		let typ = (byte >> 4) & 0x7;
+		let mut size = (byte & 0xf) as u64;
+		let mut shift = 4;
+		while byte & 0x80 != 0 {
+			pos += 1;
+			byte = *data.get(pos).ok_or_else(|| corrupt("truncated entry header"))?;
+			if shift > 57 {
+				return Err(corrupt("entry size too large"));
+			}
+			size |= ((byte & 0x7f) as u64) << shift;
+			shift += 7;
+		}
+		pos += 1;
+		let base = match typ {
+			OFS_DELTA => {
+				let mut byte = *data.get(pos).ok_or_else(|| corrupt("truncated delta offset"))?;
+				let mut back = (byte & 0x7f) as u64;
+				while byte & 0x80 != 0 {
+					pos += 1;
+					byte = *data.get(pos).ok_or_else(|| corrupt("truncated delta offset"))?;
+					back = back.checked_add(1)
+						.filter(|it| it.leading_zeros() >= 7)
+						.ok_or_else(|| corrupt("delta base out of range"))?;
+					back = (back << 7) | (byte & 0x7f) as u64;
+				}
+				pos += 1;
+				// base must be an earlier entry of pack, after its header
+				let base = offset.checked_sub(back)
+					.filter(|it| back > 0 && *it >= 12)
+					.ok_or_else(|| corrupt("delta base out of range"))?;
+				DeltaBase::Offset(base)
+			}
+			REF_DELTA => {
+				let id = data.get(pos..pos + 20)
+					.and_then(ObjectId::from_bytes)
+					.ok_or_else(|| corrupt("truncated delta base"))?;
+				pos += 20;
+				DeltaBase::Id(id)
+			}
+			_ => DeltaBase::None,
+		};
+		let size = usize::try_from(size).map_err(|_| corrupt("entry size too large"))?;
+		Ok(Entry { offset, typ, size, base, data: pos })
+	}
+
+	/// Decompress content of entry, a delta for delta entries
+	fn inflate(&self, entry: &Entry) -> Result<Vec<u8>> {
+		let data = self.data()?;
+		let mut content = Vec::with_capacity(entry.size);
+		ZlibDecoder::new(&data[entry.data..]).read_to_end(&mut content)?;
+		if content.len() != entry.size {
+			return Err(self.corrupt(entry.offset, "size does not match header"));
+		}
+		Ok(content)
+	}
+}
+
+/// Base object of pack entry
+#[derive(Debug, Copy, Clone)]
+enum DeltaBase {
+	/// Entry is a whole object
+	None,
+	/// Delta against entry at this offset of same pack
+	Offset(u64),
+	/// Delta against object with this id, possibly outside of pack
+	Id(ObjectId),
+}
+
+/// Header of pack entry
+#[derive(Debug)]
+struct Entry {
+	offset: u64,
+	typ: u8,
+	/// Size of decompressed content
+	size: usize,
+	base: DeltaBase,
+	/// Position of compressed content in pack
+	data: usize,
+}
+
+fn be_u32(data: &[u8]) -> u32 {
+	u32::from_be_bytes(data[..4].try_into().unwrap())
+}
+
+/// Read little endian base 128 size used in delta header
+fn read_varint(delta: &[u8], pos: &mut usize) -> Option<usize> {
+	let mut value = 0;
+	let mut shift = 0;
+	loop {
+		let byte = *delta.get(*pos)?;
+		*pos += 1;
+		value |= ((byte & 0x7f) as usize) << shift;
+		shift += 7;
+		if byte & 0x80 == 0 {
+			return Some(value);
+		}
+	}
+}
+
+/// Rebuild object from `base` and copy/insert instructions in `delta`
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+	let mut pos = 0;
+	if read_varint(delta, &mut pos)? != base.len() {
+		return None;
+	}
+	let size = read_varint(delta, &mut pos)?;
+	let mut out = Vec::with_capacity(size);
+	while pos < delta.len() {
+		let op = delta[pos];
+		pos += 1;
+		if op & 0x80 != 0 {
+			let mut offset = 0usize;
+			let mut len = 0usize;
+			for i in 0..4 {
+				if op & (1 << i) != 0 {
+					offset |= (*delta.get(pos)? as usize) << (i * 8);
+					pos += 1;
+				}
+			}
+			for i in 0..3 {
+				if op & (0x10 << i) != 0 {
+					len |= (*delta.get(pos)? as usize) << (i * 8);
+					pos += 1;
+				}
+			}
+			if len == 0 {
+				len = 0x10000;
+			}
+			out.extend_from_slice(base.get(offset..offset.checked_add(len)?)?);
+		} else if op != 0 {
+			let len = op as usize;
+			out.extend_from_slice(delta.get(pos..pos + len)?);
+			pos += len;
+		} else {
+			return None;
+		}
+	}
+	(out.len() == size).then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+	use std::sync::OnceLock;
+
+	use crate::error::StepdocsError;
+	use crate::git::native::object::ObjectId;
+	use crate::git::native::pack::{apply_delta, Pack, PackIndex};
+
+	/// Pack of `entries` placed after header, object `i` has id `[i + 1; 20]`
+	fn pack(entries: &[&[u8]]) -> Pack {
+		let mut data = b"PACK\0\0\0\x02".to_vec();
+		data.extend((entries.len() as u32).to_be_bytes());
+		let mut fanout = [0u32; 256];
+		let mut ids = Vec::new();
+		let mut offsets = Vec::new();
+		for (i, entry) in entries.iter().enumerate() {
+			ids.push(ObjectId([i as u8 + 1; 20]));
+			offsets.push(data.len() as u64);
+			data.extend_from_slice(entry);
+		}
+		for (i, it) in fanout.iter_mut().enumerate() {
+			*it = ids.iter().filter(|id| id.0[0] as usize <= i).count() as u32;
+		}
+		Pack {
+			path: PathBuf::from("test.pack"),
+			index: PackIndex { fanout, ids, offsets },
+			data: OnceLock::from(data),
+		}
+	}
+
+	#[test]
+	fn test_apply_delta() {
+		let base = b"hello world\n";
+		// base size 12, target size 17,
+		// copy 6 bytes from offset 0, insert "rust ", copy 6 bytes from offset 6
+		let delta = [
+			12, 17,
+			0x90, 6,
+			5, b'r', b'u', b's', b't', b' ',
+			0x91, 6, 6,
+		];
+		assert_eq!(apply_delta(base, &delta).unwrap(), b"hello rust world\n");
+		// wrong base size
+		assert!(apply_delta(b"hello", &delta).is_none());
+		// copy out of range
+		assert!(apply_delta(base, &[12, 20, 0x91, 6, 20]).is_none());
+	}
+
+	#[test]
+	fn test_invalid_delta_base() {
+		let read = |pack: &Pack, no: u8| {
+			let err = pack.read(&ObjectId([no; 20]), &|id| panic!("{id}")).unwrap_err();
+			let StepdocsError::Corrupt { reason, .. } = err else { panic!("{err:?}") };
+			reason
+		};
+		// OFS_DELTA with base offset 0, pointing at itself
+		assert_eq!(read(&pack(&[&[0x60, 0x00]]), 1), "delta base out of range");
+		// OFS_DELTA pointing before first entry
+		assert_eq!(read(&pack(&[&[0x60, 0x7f]]), 1), "delta base out of range");
+		// REF_DELTA whose base is itself
+		let mut entry = vec![0x70];
+		entry.extend([1; 20]);
+		assert_eq!(read(&pack(&[&entry]), 1), "delta chain too deep");
+	}
+}
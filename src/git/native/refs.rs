@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, StepdocsError};
+use crate::git::native::object::ObjectId;
+
+/// Symbolic refs deeper than this are treated as a loop
+const MAX_SYMREF_DEPTH: usize = 5;
+
+/// Loose and packed refs of repository
+#[derive(Debug)]
+pub struct Refs {
+	/// Directory holding `HEAD` and other per-worktree refs
+	git_dir: PathBuf,
+	/// Directory holding `refs/` and `packed-refs`
+	common_dir: PathBuf,
+}
+
+impl Refs {
+	pub fn new(git_dir: impl Into<PathBuf>, common_dir: impl Into<PathBuf>) -> Self {
+		Self {
+			git_dir: git_dir.into(),
+			common_dir: common_dir.into(),
+		}
+	}
+
+	/// Path of loose ref, `None` for names which are neither under `refs/` nor HEAD-style like `ORIG_HEAD`
+	fn ref_path(&self, name: &str) -> Option<PathBuf> {
+		if name.split('/').any(|it| it.is_empty() || it == "." || it == "..") {
+			return None;
+		}
+		if name.starts_with("refs/") {
+			Some(self.common_dir.join(name))
+		} else if name.ends_with("HEAD") && name.bytes().all(|it| it.is_ascii_uppercase() || it == b'_') {
+			Some(self.git_dir.join(name))
+		} else {
+			None
+		}
+	}
+
+	/// Resolve full ref name such as `HEAD` or `refs/heads/main`, following symbolic refs,
+	/// other names like `config` are never read from git directory
+	pub fn resolve(&self, name: &str) -> Result<Option<ObjectId>> {
+		let mut name = name.to_string();
+		for _ in 0..MAX_SYMREF_DEPTH {
+			let Some(path) = self.ref_path(&name) else {
+				return Ok(None);
+			};
+			let content = match std::fs::read_to_string(path) {
+				Ok(it) => it,
+				Err(err) if matches!(err.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::IsADirectory) => {
+					return self.packed_ref(&name);
+				}
+				Err(err) => return Err(err.into()),
+			};
+			match content.trim().strip_prefix("ref:") {
+				Some(target) => name = target.trim().to_string(),
+				None => return content.parse().map(Some)
+					.map_err(|_| StepdocsError::corrupt(format!("ref {name}"), "invalid object id")),
+			}
+		}
+		Err(StepdocsError::corrupt(format!("ref {name}"), "too many levels of symbolic refs"))
+	}
+
+	fn packed_ref(&self, name: &str) -> Result<Option<ObjectId>> {
+		Ok(self.packed()?.into_iter()
+			.find(|(it, _)| it == name)
+			.map(|(_, id)| id))
+	}
+
+	/// Entries of `packed-refs`, peeled `^id` lines are ignored
+	fn packed(&self) -> Result<Vec<(String, ObjectId)>> {
+		let content = match std::fs::read_to_string(self.common_dir.join("packed-refs")) {
+			Ok(it) => it,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+			Err(err) => return Err(err.into()),
+		};
+		Ok(content.lines()
+			.filter(|it| !it.starts_with('#') && !it.starts_with('^'))
+			.filter_map(|it| it.split_once(' '))
+			.filter_map(|(id, name)| Some((name.trim().to_string(), id.parse().ok()?)))
+			.collect())
+	}
+
+	/// Every ref under `refs/` sorted by name, loose refs take precedence over packed ones
+	pub fn all(&self) -> Result<Vec<(String, ObjectId)>> {
+		let mut refs = Vec::new();
+		let mut names = Vec::new();
+		collect_loose(&self.common_dir, &self.common_dir.join("refs"), &mut names)?;
+		for name in names {
+			if let Some(id) = self.resolve(&name)? {
+				refs.push((name, id));
+			}
+		}
+		for (name, id) in self.packed()? {
+			if !refs.iter().any(|(it, _)| *it == name) {
+				refs.push((name, id));
+			}
+		}
+		refs.sort();
+		Ok(refs)
+	}
+}
+
+fn collect_loose(root: &Path, dir: &Path, names: &mut Vec<String>) -> Result<()> {
+	let entries = match std::fs::read_dir(dir) {
+		Ok(it) => it,
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+		Err(err) => return Err(err.into()),
+	};
+	for entry in entries {
+		let path = entry?.path();
+		if path.is_dir() {
+			collect_loose(root, &path, names)?;
+		} else if let Ok(name) = path.strip_prefix(root) {
+			names.push(name.to_string_lossy().replace('\\', "/"));
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,183 @@
+use std::collections::BTreeSet;
+
+use crate::error::Result;
+use crate::git::algorithm::similarity;
+use crate::git::native::{NativeRepository, ObjectId, TreeEntry};
+use crate::git::{BinaryInfo, ChangeKind, DiffInfo, DiffOptions, Patch};
+
+/// Pairs needing more comparisons than this are not checked for inexact renames
+const MAX_RENAME_PAIRS: usize = 10_000;
+/// Minimum similarity percentage of inexact rename, same as git
+const RENAME_THRESHOLD: u8 = 50;
+/// Number of bytes checked for NUL when deciding if file is binary, same as git
+const BINARY_PROBE: usize = 8000;
+const SUBMODULE_MODE: u32 = 0o160000;
+
+/// File on one side of tree comparison
+#[derive(Debug, Clone)]
+struct Side {
+	path: String,
+	mode: u32,
+	id: ObjectId,
+}
+
+/// Old and new file of a change, with similarity if it was paired by rename detection
+type Change = (Option<Side>, Option<Side>, Option<u8>);
+
+impl NativeRepository {
+	/// Changes made by `commit` against its first parent (or empty tree for root commit) ordered by path
+	pub fn show(&self, commit: &ObjectId, options: &DiffOptions) -> Result<Vec<DiffInfo>> {
+		let commit = self.read_commit(commit)?;
+		let parent = match commit.parents.first() {
+			Some(parent) => Some(self.read_commit(parent)?.tree),
+			None => None,
+		};
+		let mut changes = Vec::new();
+		self.diff_trees(parent, Some(commit.tree), "", &mut changes)?;
+		let mut changes = self.detect_renames(changes)?;
+		changes.sort_by(|a, b| path_of(a).cmp(path_of(b)));
+		changes.iter()
+			.map(|(old, new, score)| self.diff_info(old.as_ref(), new.as_ref(), *score, options))
+			.collect()
+	}
+
+	/// Collect changed files between two trees recursively
+	fn diff_trees(&self, old: Option<ObjectId>, new: Option<ObjectId>, prefix: &str, changes: &mut Vec<Change>) -> Result<()> {
+		if old == new {
+			return Ok(());
+		}
+		let read = |id: Option<ObjectId>| -> Result<Vec<TreeEntry>> {
+			match id {
+				Some(id) => Ok(self.read_tree(&id)?.entries),
+				None => Ok(vec![]),
+			}
+		};
+		let (old, new) = (read(old)?, read(new)?);
+		let names = old.iter().chain(&new).map(|it| it.name.as_str()).collect::<BTreeSet<_>>();
+		for name in names {
+			let path = format!("{prefix}{name}");
+			let find = |entries: &[TreeEntry]| entries.iter().find(|it| it.name == name).cloned();
+			let (old, new) = (find(&old), find(&new));
+			// file replaced by directory is deletion and additions
+			let tree = |it: &Option<TreeEntry>| it.as_ref().filter(|it| it.is_tree()).map(|it| it.id);
+			if tree(&old).is_some() || tree(&new).is_some() {
+				self.diff_trees(tree(&old), tree(&new), &format!("{path}/"), changes)?;
+			}
+			let side = |it: Option<TreeEntry>| it.filter(|it| !it.is_tree()).map(|it| Side {
+				path: path.clone(),
+				mode: it.mode,
+				id: it.id,
+			});
+			match (side(old), side(new)) {
+				(Some(old), Some(new)) if old.id == new.id && old.mode == new.mode => {}
+				(None, None) => {}
+				(old, new) => changes.push((old, new, None)),
+			}
+		}
+		Ok(())
+	}
+
+	/// Pair deleted and added files with same or similar content
+	fn detect_renames(&self, changes: Vec<Change>) -> Result<Vec<Change>> {
+		let (mut deleted, mut rest): (Vec<_>, Vec<_>) = changes.into_iter()
+			.partition(|it| it.0.is_some() && it.1.is_none());
+		let (mut added, mut result): (Vec<_>, Vec<_>) = rest.drain(..)
+			.partition(|it| it.0.is_none() && it.1.is_some());
+		// exact renames
+		added.retain_mut(|add| {
+			let new = add.1.as_ref().unwrap();
+			match deleted.iter().position(|it| it.0.as_ref().unwrap().id == new.id) {
+				Some(at) => {
+					result.push((deleted.remove(at).0, add.1.take(), Some(100)));
+					false
+				}
+				None => true,
+			}
+		});
+		if !deleted.is_empty() && !added.is_empty() && deleted.len() * added.len() <= MAX_RENAME_PAIRS {
+			let content = |side: &Option<Side>| -> Result<Option<String>> {
+				let data = self.side_content(side.as_ref().unwrap())?;
+				Ok((!is_binary(&data)).then(|| String::from_utf8_lossy(&data).into_owned()))
+			};
+			let old = deleted.iter().map(|it| content(&it.0)).collect::<Result<Vec<_>>>()?;
+			let new = added.iter().map(|it| content(&it.1)).collect::<Result<Vec<_>>>()?;
+			let mut scores = Vec::new();
+			for (i, old) in old.iter().enumerate() {
+				for (j, new) in new.iter().enumerate() {
+					if let (Some(old), Some(new)) = (old, new) {
+						let score = similarity(old, new);
+						if score >= RENAME_THRESHOLD {
+							scores.push((score, i, j));
+						}
+					}
+				}
+			}
+			// best pairs first, each file is used once
+			scores.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+			for (score, i, j) in scores {
+				if deleted[i].0.is_some() && added[j].1.is_some() {
+					result.push((deleted[i].0.take(), added[j].1.take(), Some(score)));
+				}
+			}
+		}
+		result.extend(deleted.into_iter().filter(|it| it.0.is_some()));
+		result.extend(added.into_iter().filter(|it| it.1.is_some()));
+		Ok(result)
+	}
+
+	fn side_content(&self, side: &Side) -> Result<Vec<u8>> {
+		// git show gitlink as a single line
+		if side.mode == SUBMODULE_MODE {
+			return Ok(format!("Subproject commit {}\n", side.id).into_bytes());
+		}
+		self.read_blob(&side.id)
+	}
+
+	fn diff_info(&self, old: Option<&Side>, new: Option<&Side>, score: Option<u8>, options: &DiffOptions) -> Result<DiffInfo> {
+		let path = |side: Option<&Side>| side.map(|it| it.path.clone());
+		let (old_path, new_path) = (path(old), path(new));
+		let mut info = DiffInfo::new(format!(
+			"diff --git a/{} b/{}",
+			old_path.as_deref().or(new_path.as_deref()).unwrap_or_default(),
+			new_path.as_deref().or(old_path.as_deref()).unwrap_or_default(),
+		));
+		info.kind = match (old, new) {
+			(None, _) => ChangeKind::Added,
+			(_, None) => ChangeKind::Deleted,
+			(Some(old), Some(new)) if old.path != new.path => ChangeKind::Renamed,
+			_ => ChangeKind::Modified,
+		};
+		info.old_path = old_path;
+		info.new_path = new_path;
+		info.old_mode = old.map(|it| it.mode);
+		info.new_mode = new.map(|it| it.mode);
+		info.similarity = score.filter(|_| info.kind == ChangeKind::Renamed);
+		let id = |side: Option<&Side>| side.map(|it| it.id).unwrap_or_default();
+		info.index = Some(format!("{}..{}", id(old), id(new)));
+
+		let old_data = old.map(|it| self.side_content(it)).transpose()?.unwrap_or_default();
+		let new_data = new.map(|it| self.side_content(it)).transpose()?.unwrap_or_default();
+		if is_binary(&old_data) || is_binary(&new_data) {
+			info.binary = Some(BinaryInfo {
+				old_id: old.map(|it| it.id.to_hex()),
+				new_id: new.map(|it| it.id.to_hex()),
+				old_size: old.map(|_| old_data.len() as u64),
+				new_size: new.map(|_| new_data.len() as u64),
+			});
+			return Ok(info);
+		}
+		info.diffs = Patch::diff(&String::from_utf8_lossy(&old_data), &String::from_utf8_lossy(&new_data), options);
+		if info.kind == ChangeKind::Modified && info.old_mode != info.new_mode && info.diffs.patches() == 0 {
+			info.kind = ChangeKind::ModeChange;
+		}
+		Ok(info)
+	}
+}
+
+fn path_of(change: &Change) -> &str {
+	change.1.as_ref().or(change.0.as_ref()).map(|it| it.path.as_str()).unwrap_or_default()
+}
+
+fn is_binary(data: &[u8]) -> bool {
+	data[..data.len().min(BINARY_PROBE)].contains(&0)
+}
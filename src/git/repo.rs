@@ -1,31 +1,434 @@
-use std::io;
-use std::path::PathBuf;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
 
-use crate::git::diff::GitDiffParser;
-use crate::git::log_parser::GitLogParser;
-use crate::RawOutputMessage;
-use crate::util::proc::{run_process, spawn};
+use serde::Deserialize;
 
-pub struct GitRepository(PathBuf);
+use crate::error::{Result, StepdocsError};
+use crate::git::algorithm::DiffOptions;
+use crate::git::diff::{DiffInfo, GitDiffParser};
+use crate::git::log_options::LogOptions;
+use crate::git::log_parser::{GitLog, GitLogParser};
+use crate::git::native::{NativeRepository, ObjectId};
+use crate::util::iter::AsyncIterator;
+use crate::util::proc::{RawOutputMessage, run_process, spawn};
+
+/// Implementation used to read repository
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackend {
+	/// Spawn `git` binary for every query
+	#[default]
+	Process,
+	/// Read refs, loose objects and pack files directly
+	Native,
+}
+
+impl Display for GitBackend {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			GitBackend::Process => "process",
+			GitBackend::Native => "native",
+		})
+	}
+}
+
+impl FromStr for GitBackend {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"process" | "git" => Ok(GitBackend::Process),
+			"native" => Ok(GitBackend::Native),
+			_ => Err(format!("Unknown git backend `{s}`")),
+		}
+	}
+}
+
+/// File listed by [GitRepository::ls_tree]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TreeFile {
+	/// Path relative to repository root
+	pub path: String,
+	pub mode: u32,
+	pub id: String,
+}
+
+/// Tag listed by [GitRepository::tags]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GitTag {
+	/// Name without `refs/tags/`
+	pub name: String,
+	/// Commit pointed to, annotated tags are peeled
+	pub commit: String,
+	/// Message of annotated tag, `None` for lightweight tag
+	pub message: Option<String>,
+}
+
+pub struct GitRepository {
+	path: PathBuf,
+	native: Option<NativeRepository>,
+	diff_options: DiffOptions,
+}
 
 impl GitRepository {
+	/// Repository read by `git` binary
 	pub fn new(path: impl Into<PathBuf>) -> Self {
-		Self(path.into())
+		Self {
+			path: path.into(),
+			native: None,
+			diff_options: DiffOptions::default(),
+		}
+	}
+
+	/// Open repository with `backend`, native backend fail if `path` is not a repository
+	pub fn open(path: impl Into<PathBuf>, backend: GitBackend) -> Result<Self> {
+		let path = path.into();
+		let native = match backend {
+			GitBackend::Process => None,
+			GitBackend::Native => Some(NativeRepository::open(&path)?),
+		};
+		Ok(Self {
+			path,
+			native,
+			diff_options: DiffOptions::default(),
+		})
+	}
+
+	/// Algorithm and context size used by [Self::show]
+	pub fn with_diff_options(mut self, options: DiffOptions) -> Self {
+		self.diff_options = options;
+		self
+	}
+
+	pub fn diff_options(&self) -> &DiffOptions {
+		&self.diff_options
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	pub fn backend(&self) -> GitBackend {
+		match self.native {
+			Some(_) => GitBackend::Native,
+			None => GitBackend::Process,
+		}
+	}
+
+	/// Object reader of native backend
+	pub fn native(&self) -> Option<&NativeRepository> {
+		self.native.as_ref()
 	}
 
 	pub async fn init(&self) -> RawOutputMessage {
-		run_process("git", ["init"], &self.0).await.into()
+		run_process("git", ["init"], &self.path).await.into()
 	}
 
-	pub fn log(&self) -> io::Result<GitLogParser> {
-		let mut child = spawn("git", ["log", "--all", "--pretty=format:%H%n%aN <%aE>%n%ad%n%s%n==END=="], &self.0)?;
+	/// List commits selected by `options`
+	pub fn log(&self, options: &LogOptions) -> Result<GitLogStream> {
+		if let Some(native) = &self.native {
+			return Ok(GitLogStream::Native(native.log(options)?.into_iter()));
+		}
+		let mut child = spawn("git", options.args()?, &self.path)?;
 		let stdout = child.stdout.take().unwrap();
-		Ok(GitLogParser::new(child, stdout))
+		Ok(GitLogStream::Process(Box::new(GitLogParser::new(child, stdout))))
 	}
 
-	pub fn show(&self, commit: &str) -> io::Result<GitDiffParser> {
-		let mut child = spawn("git", ["show", "--pretty=format:", commit], &self.0)?;
+	/// Changes made by `commit` against its first parent
+	pub fn show(&self, commit: &str) -> Result<GitDiffStream> {
+		if let Some(native) = &self.native {
+			let diffs = native.show(&native.resolve(commit)?, &self.diff_options)?;
+			return Ok(GitDiffStream::Native(diffs.into_iter()));
+		}
+		let DiffOptions { algorithm, context } = self.diff_options;
+		let mut child = spawn("git", [
+			"show",
+			"--pretty=format:",
+			// merge is diffed against first parent like native backend, instead of `diff --cc`
+			"--diff-merges=first-parent",
+			// user config must not change output understood by parser
+			"--no-color",
+			"--no-ext-diff",
+			"--src-prefix=a/",
+			"--dst-prefix=b/",
+			"--full-index",
+			"--indent-heuristic",
+			&format!("--diff-algorithm={algorithm}"),
+			&format!("-U{context}"),
+			commit,
+		], &self.path)?;
 		let stdout = child.stdout.take().unwrap();
-		Ok(GitDiffParser::new(child, stdout))
+		Ok(GitDiffStream::Process(Box::new(GitDiffParser::new(child, stdout).with_commit(commit))))
+	}
+
+	/// Read content of blob `id`
+	pub async fn read_blob(&self, id: &str) -> Result<Vec<u8>> {
+		if let Some(native) = &self.native {
+			return native.read_blob(&id.parse::<ObjectId>()?);
+		}
+		self.run(["cat-file", "blob", id]).await
+	}
+
+	/// Read content of file at `path` in revision `rev`
+	pub async fn cat_file(&self, rev: &str, path: &str) -> Result<Vec<u8>> {
+		if let Some(native) = &self.native {
+			let not_found = || StepdocsError::NotFound(format!("{rev}:{path}"));
+			let entry = native.find_entry(&native.resolve(rev)?, path)?.ok_or_else(not_found)?;
+			if entry.is_tree() || entry.is_submodule() {
+				return Err(not_found());
+			}
+			return native.read_blob(&entry.id);
+		}
+		self.run(["cat-file", "blob", &format!("{rev}:{path}")]).await
+	}
+
+	/// List every file in revision `rev` recursively
+	pub async fn ls_tree(&self, rev: &str) -> Result<Vec<TreeFile>> {
+		if let Some(native) = &self.native {
+			let files = native.ls_tree(&native.resolve(rev)?)?;
+			return Ok(files.into_iter()
+				.map(|(path, entry)| TreeFile {
+					path,
+					mode: entry.mode,
+					id: entry.id.to_hex(),
+				})
+				.collect());
+		}
+		let output = self.run(["ls-tree", "-r", "-z", "--full-tree", rev]).await?;
+		// `<mode> <type> <id>\t<path>\0`
+		output.split(|it| *it == 0)
+			.filter(|it| !it.is_empty())
+			.map(|entry| {
+				let entry = String::from_utf8_lossy(entry);
+				let corrupt = || StepdocsError::corrupt(format!("ls-tree {rev}"), entry.to_string());
+				let (info, path) = entry.split_once('\t').ok_or_else(corrupt)?;
+				let mut info = info.split(' ');
+				let (Some(mode), Some(_), Some(id)) = (info.next(), info.next(), info.next()) else {
+					return Err(corrupt());
+				};
+				Ok(TreeFile {
+					path: path.to_string(),
+					mode: u32::from_str_radix(mode, 8).map_err(|_| corrupt())?,
+					id: id.to_string(),
+				})
+			})
+			.collect()
+	}
+
+	/// List tags pointing to commits sorted by name
+	pub async fn tags(&self) -> Result<Vec<GitTag>> {
+		let mut tags = match &self.native {
+			Some(native) => native.tags()?,
+			None => {
+				// `<types and ids> <name>\0<subject>\0<body>\0\n`, peeled object is empty for lightweight tags
+				let output = self.run([
+					"for-each-ref",
+					"--format=%(objecttype) %(objectname) %(*objecttype) %(*objectname) %(refname:strip=2)%00%(contents:subject)%00%(contents:body)%00",
+					"refs/tags",
+				]).await?;
+				let mut tags = Vec::new();
+				for (mut tag, nested) in parse_tags(&String::from_utf8_lossy(&output)) {
+					// `%(*objectname)` is peeled once, tag of a tag is peeled until commit
+					if nested {
+						let Ok(commit) = self.run(["rev-parse", "--verify", "-q", &format!("{}^{{commit}}", tag.commit)]).await else {
+							continue;
+						};
+						tag.commit = String::from_utf8_lossy(&commit).trim().to_string();
+					}
+					tags.push(tag);
+				}
+				tags
+			}
+		};
+		tags.sort_by(|a, b| a.name.cmp(&b.name));
+		Ok(tags)
+	}
+
+	/// Get size of object `id` in bytes
+	pub async fn object_size(&self, id: &str) -> Result<u64> {
+		if let Some(native) = &self.native {
+			return Ok(native.read_object(&id.parse::<ObjectId>()?)?.data.len() as u64);
+		}
+		let size = self.run(["cat-file", "-s", id]).await?;
+		String::from_utf8_lossy(&size)
+			.trim()
+			.parse()
+			.map_err(|err| StepdocsError::Git {
+				command: format!("cat-file -s {id}"),
+				message: format!("{err}"),
+			})
+	}
+
+	/// Run git command and return its stdout
+	async fn run<const N: usize>(&self, args: [&str; N]) -> Result<Vec<u8>> {
+		match run_process("git", args, &self.path).await? {
+			RawOutputMessage::Success(output) => Ok(output),
+			RawOutputMessage::Error(err) => Err(StepdocsError::Git {
+				command: args.join(" "),
+				message: String::from_utf8_lossy(&err).trim().to_string(),
+			}),
+		}
+	}
+}
+
+/// Parse `for-each-ref` records of [GitRepository::tags] and whether commit of the tag is still a tag to be peeled,
+/// tags of trees and blobs are left out
+fn parse_tags(output: &str) -> Vec<(GitTag, bool)> {
+	let fields = output.split('\0').collect::<Vec<_>>();
+	fields.chunks_exact(3)
+		.filter_map(|record| {
+			let header = record[0].trim_start_matches('\n');
+			let message = || format!("{}\n\n{}", record[1], record[2]).trim().to_string();
+			let (commit, name, message, nested) = match header.splitn(5, ' ').collect::<Vec<_>>()[..] {
+				["commit", id, "", "", name] => (id, name, None, false),
+				["tag", _, "commit", id, name] => (id, name, Some(message()), false),
+				["tag", _, "tag", id, name] => (id, name, Some(message()), true),
+				_ => return None,
+			};
+			Some((GitTag { name: name.to_string(), commit: commit.to_string(), message }, nested))
+		})
+		.collect()
+}
+
+/// Commits listed by either backend
+pub enum GitLogStream {
+	Process(Box<GitLogParser>),
+	Native(std::vec::IntoIter<GitLog>),
+}
+
+impl AsyncIterator<StepdocsError> for GitLogStream {
+	type Item = GitLog;
+
+	fn next<'a>(&'a mut self) -> Pin<Box<dyn Future<Output=Result<Option<Self::Item>>> + 'a>> {
+		match self {
+			GitLogStream::Process(parser) => parser.next(),
+			GitLogStream::Native(logs) => {
+				let log = logs.next();
+				Box::pin(async move { Ok(log) })
+			}
+		}
+	}
+}
+
+/// Changed files listed by either backend
+pub enum GitDiffStream {
+	Process(Box<GitDiffParser>),
+	Native(std::vec::IntoIter<DiffInfo>),
+}
+
+impl AsyncIterator<StepdocsError> for GitDiffStream {
+	type Item = DiffInfo;
+
+	fn next<'a>(&'a mut self) -> Pin<Box<dyn Future<Output=Result<Option<Self::Item>>> + 'a>> {
+		match self {
+			GitDiffStream::Process(parser) => parser.next(),
+			GitDiffStream::Native(diffs) => {
+				let diff = diffs.next();
+				Box::pin(async move { Ok(diff) })
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::error::StepdocsError;
+	use crate::git::fixture::TestRepo;
+	use crate::git::repo::parse_tags;
+	use crate::git::{GitBackend, GitRepository, GitTag};
+	use crate::util::iter::AsyncIterator;
+
+	#[test]
+	fn test_parse_tags() {
+		let output = "commit c1   light\0Add a\0\0\n\
+			tag t2 commit c2 v1\0Chapter one\0Learn the basics.\n\nWith two paragraphs.\n\0\n\
+			tag t3 tree d3 snapshot\0Tree\0\0\n\
+			tag t4 commit c4 empty\0\0\0\n\
+			tag t5 tag t2 nested\0Nested\0\0\n";
+		assert_eq!(parse_tags(output), [
+			(GitTag { name: "light".to_string(), commit: "c1".to_string(), message: None }, false),
+			(GitTag {
+				name: "v1".to_string(),
+				commit: "c2".to_string(),
+				message: Some("Chapter one\n\nLearn the basics.\n\nWith two paragraphs.".to_string()),
+			}, false),
+			(GitTag { name: "empty".to_string(), commit: "c4".to_string(), message: Some(String::new()) }, false),
+			(GitTag { name: "nested".to_string(), commit: "t2".to_string(), message: Some("Nested".to_string()) }, true),
+		]);
+	}
+
+	#[tokio::test]
+	async fn test_tags() {
+		let test = TestRepo::new("tags");
+		let first = test.commit("First");
+		test.git(&["tag", "-a", "v1", "-m", "Chapter one\n\nLearn the *basics*."]);
+		let second = test.commit("Second");
+		test.git(&["tag", "light"]);
+		test.git(&["tag", "-a", "nested", "-m", "Points to tag", "v1"]);
+		for backend in [GitBackend::Process, GitBackend::Native] {
+			let repo = GitRepository::open(test.path(), backend).unwrap();
+			let tags = repo.tags().await.unwrap();
+			assert_eq!(tags, [
+				GitTag { name: "light".to_string(), commit: second.clone(), message: None },
+				GitTag { name: "nested".to_string(), commit: first.clone(), message: Some("Points to tag".to_string()) },
+				GitTag { name: "v1".to_string(), commit: first.clone(), message: Some("Chapter one\n\nLearn the *basics*.".to_string()) },
+			], "{backend}");
+		}
+	}
+
+	#[tokio::test]
+	async fn test_show_merge() {
+		let test = TestRepo::new("show-merge");
+		test.write("f", "base\n");
+		test.commit("Base");
+		test.git(&["checkout", "-q", "-b", "side"]);
+		test.write("f", "side\n");
+		test.commit("Side");
+		test.git(&["checkout", "-q", "main"]);
+		test.write("f", "main\n");
+		test.commit("Main");
+		// conflict resolved with content of neither parent
+		test.git(&["merge", "-q", "-s", "ours", "--no-commit", "side"]);
+		test.write("f", "resolved\n");
+		let merge = test.commit("Merge side");
+		for (key, value) in [("color.ui", "always"), ("diff.noprefix", "true"), ("diff.mnemonicPrefix", "true")] {
+			test.git(&["config", key, value]);
+		}
+		for backend in [GitBackend::Process, GitBackend::Native] {
+			let repo = GitRepository::open(test.path(), backend).unwrap();
+			let diffs = repo.show(&merge).unwrap().collect().await.unwrap();
+			assert_eq!(diffs.len(), 1, "{backend}");
+			assert_eq!(diffs[0].path(), "f");
+			assert_eq!(diffs[0].diffs.apply("main\n").unwrap(), "resolved\n", "{backend}");
+		}
+	}
+
+	#[tokio::test]
+	async fn test_read_tree() {
+		let test = TestRepo::new("read-tree");
+		test.write("readme.md", "# Demo\n");
+		test.write("src/git/mod.rs", "mod repo;\n");
+		test.commit("Add files");
+		test.write("src/git/mod.rs", "mod repo;\nmod diff;\n");
+		// names are not quoted with `-z`
+		test.write("docs/\u{fc}ber\tplan.md", "plan\n");
+		let head = test.commit("Extend files");
+		for backend in [GitBackend::Process, GitBackend::Native] {
+			let repo = GitRepository::open(test.path(), backend).unwrap();
+			assert_eq!(repo.cat_file(&head, "src/git/mod.rs").await.unwrap(), b"mod repo;\nmod diff;\n");
+			assert_eq!(repo.cat_file("HEAD~1", "src/git/mod.rs").await.unwrap(), b"mod repo;\n");
+			assert!(repo.cat_file(&head, "src/missing.rs").await.is_err());
+			assert!(repo.cat_file(&head, "src/git").await.is_err(), "{backend}: tree is not a file");
+
+			let files = repo.ls_tree(&head).await.unwrap();
+			let paths = files.iter().map(|it| it.path.as_str()).collect::<Vec<_>>();
+			assert_eq!(paths, ["docs/\u{fc}ber\tplan.md", "readme.md", "src/git/mod.rs"], "{backend}");
+			assert!(files.iter().all(|it| it.mode == 0o100644 && it.id.len() == 40));
+			assert_eq!(repo.ls_tree("HEAD~1").await.unwrap().len(), 2);
+			assert!(matches!(repo.ls_tree("missing").await, Err(StepdocsError::Git { .. } | StepdocsError::NotFound(_))));
+		}
 	}
-}
\ No newline at end of file
+}
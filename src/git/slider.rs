@@ -0,0 +1,403 @@
+use crate::git::algorithm::Edit;
+
+/// Indentation wider than this is treated as equal
+const MAX_INDENT: i32 = 200;
+/// Blank lines further than this from a split are not counted
+const MAX_BLANKS: i32 = 20;
+/// Groups which can slide further than this only try positions near the end
+const MAX_SLIDING: usize = 100;
+
+// weights of git's indent heuristic (xdiff/xdiffi.c)
+const START_OF_FILE_PENALTY: i32 = 1;
+const END_OF_FILE_PENALTY: i32 = 21;
+const TOTAL_BLANK_WEIGHT: i32 = -30;
+const POST_BLANK_WEIGHT: i32 = 6;
+const RELATIVE_INDENT_PENALTY: i32 = -4;
+const RELATIVE_INDENT_WITH_BLANK_PENALTY: i32 = 10;
+const RELATIVE_OUTDENT_PENALTY: i32 = 24;
+const RELATIVE_OUTDENT_WITH_BLANK_PENALTY: i32 = 17;
+const RELATIVE_DEDENT_PENALTY: i32 = 23;
+const RELATIVE_DEDENT_WITH_BLANK_PENALTY: i32 = 17;
+const INDENT_WEIGHT: i32 = 60;
+
+/// Slide every group of changed lines whose position is ambiguous (the lines around it repeat)
+/// to the place a human would choose: next to a change of the other file, otherwise between
+/// blank lines and at block boundaries, same as git's indent heuristic
+pub(crate) fn compact(old: &[&str], new: &[&str], edits: &[Edit]) -> Vec<Edit> {
+	let mut old = Side::new(old);
+	let mut new = Side::new(new);
+	let (mut o, mut n) = (0, 0);
+	for edit in edits {
+		match edit {
+			Edit::Equal => (o, n) = (o + 1, n + 1),
+			Edit::Delete => {
+				old.changed[o + 1] = true;
+				o += 1;
+			}
+			Edit::Insert => {
+				new.changed[n + 1] = true;
+				n += 1;
+			}
+		}
+	}
+	old.compact(&new);
+	new.compact(&old);
+
+	// rebuild edit script with deletions before insertions
+	let mut edits = Vec::with_capacity(edits.len());
+	let (mut o, mut n) = (0, 0);
+	while o < old.lines.len() || n < new.lines.len() {
+		if o < old.lines.len() && old.is_changed(o) {
+			edits.push(Edit::Delete);
+			o += 1;
+		} else if n < new.lines.len() && new.is_changed(n) {
+			edits.push(Edit::Insert);
+			n += 1;
+		} else {
+			edits.push(Edit::Equal);
+			o += 1;
+			n += 1;
+		}
+	}
+	edits
+}
+
+/// Lines of one file with change flags, `changed[0]` and `changed[len + 1]` are unchanged sentinels
+struct Side<'a, 'b> {
+	lines: &'b [&'a str],
+	changed: Vec<bool>,
+}
+
+/// Changed lines `start..end` (empty range is a position between unchanged lines)
+#[derive(Debug, Copy, Clone)]
+struct Group {
+	start: usize,
+	end: usize,
+}
+
+impl Group {
+	fn is_empty(&self) -> bool {
+		self.start == self.end
+	}
+}
+
+impl<'a, 'b> Side<'a, 'b> {
+	fn new(lines: &'b [&'a str]) -> Self {
+		Self {
+			lines,
+			changed: vec![false; lines.len() + 2],
+		}
+	}
+
+	fn is_changed(&self, line: usize) -> bool {
+		self.changed[line + 1]
+	}
+
+	fn set_changed(&mut self, line: usize, changed: bool) {
+		self.changed[line + 1] = changed;
+	}
+
+	/// Changed lines at the start of file (possibly empty)
+	fn first_group(&self) -> Group {
+		let mut end = 0;
+		while self.is_changed(end) {
+			end += 1;
+		}
+		Group { start: 0, end }
+	}
+
+	/// Move to the group after next unchanged line, `false` at end of file
+	fn next_group(&self, group: &mut Group) -> bool {
+		if group.end == self.lines.len() {
+			return false;
+		}
+		group.start = group.end + 1;
+		group.end = group.start;
+		while self.is_changed(group.end) {
+			group.end += 1;
+		}
+		true
+	}
+
+	/// Move to the group before previous unchanged line, `false` at start of file
+	fn previous_group(&self, group: &mut Group) -> bool {
+		if group.start == 0 {
+			return false;
+		}
+		group.end = group.start - 1;
+		group.start = group.end;
+		while group.start > 0 && self.is_changed(group.start - 1) {
+			group.start -= 1;
+		}
+		true
+	}
+
+	/// Shift group one line down if its first line equals the line after it, merging with following group
+	fn slide_down(&mut self, group: &mut Group) -> bool {
+		if group.end >= self.lines.len() || self.lines[group.start] != self.lines[group.end] {
+			return false;
+		}
+		self.set_changed(group.start, false);
+		self.set_changed(group.end, true);
+		group.start += 1;
+		group.end += 1;
+		while self.is_changed(group.end) {
+			group.end += 1;
+		}
+		true
+	}
+
+	/// Shift group one line up if its last line equals the line before it, merging with preceding group
+	fn slide_up(&mut self, group: &mut Group) -> bool {
+		if group.start == 0 || self.lines[group.start - 1] != self.lines[group.end - 1] {
+			return false;
+		}
+		group.start -= 1;
+		group.end -= 1;
+		self.set_changed(group.start, true);
+		self.set_changed(group.end, false);
+		while group.start > 0 && self.is_changed(group.start - 1) {
+			group.start -= 1;
+		}
+		true
+	}
+
+	/// Slide groups of this file, `other` is only used to keep changes of both files together
+	fn compact(&mut self, other: &Side) {
+		let mut group = self.first_group();
+		let mut other_group = other.first_group();
+		loop {
+			if !group.is_empty() {
+				let (earliest_end, matching_other) = self.slide_range(&mut group, other, &mut other_group);
+				if group.end == earliest_end {
+					// group can not move
+				} else if let Some(end) = matching_other {
+					// keep deletion and insertion of the same change together
+					while group.end > end {
+						self.slide_up(&mut group);
+						other.previous_group(&mut other_group);
+					}
+				} else {
+					let best = self.best_shift(group, earliest_end);
+					while group.end > best {
+						self.slide_up(&mut group);
+						other.previous_group(&mut other_group);
+					}
+				}
+			}
+			if !self.next_group(&mut group) || !other.next_group(&mut other_group) {
+				break;
+			}
+		}
+	}
+
+	/// Slide group up and then down as far as possible, merging with neighbours until its size is stable,
+	/// return earliest end and last end where group is next to a change of other file
+	fn slide_range(&mut self, group: &mut Group, other: &Side, other_group: &mut Group) -> (usize, Option<usize>) {
+		loop {
+			let size = group.end - group.start;
+			let mut matching_other = None;
+			while self.slide_up(group) {
+				other.previous_group(other_group);
+			}
+			let earliest_end = group.end;
+			if !other_group.is_empty() {
+				matching_other = Some(group.end);
+			}
+			while self.slide_down(group) {
+				other.next_group(other_group);
+				if !other_group.is_empty() {
+					matching_other = Some(group.end);
+				}
+			}
+			if size == group.end - group.start {
+				return (earliest_end, matching_other);
+			}
+		}
+	}
+
+	/// End of group at the best scored position, group is at its lowest position
+	fn best_shift(&self, group: Group, earliest_end: usize) -> usize {
+		let size = group.end - group.start;
+		let first = earliest_end
+			.max((group.end - size).saturating_sub(1))
+			.max(group.end.saturating_sub(MAX_SLIDING));
+		let mut best: Option<(usize, SplitScore)> = None;
+		for shift in first..=group.end {
+			let mut score = SplitScore::default();
+			score.add(&self.measure_split(shift));
+			score.add(&self.measure_split(shift - size));
+			// prefer lower position on tie
+			if best.as_ref().is_none_or(|(_, best)| score.cmp(best) <= 0) {
+				best = Some((shift, score));
+			}
+		}
+		best.map(|it| it.0).unwrap_or(group.end)
+	}
+
+	/// Describe blank lines and indentation around the split before line `split`
+	fn measure_split(&self, split: usize) -> SplitMeasurement {
+		let end_of_file = split >= self.lines.len();
+		let mut measure = SplitMeasurement {
+			end_of_file,
+			indent: if end_of_file { -1 } else { indent(self.lines[split]) },
+			pre_blank: 0,
+			pre_indent: -1,
+			post_blank: 0,
+			post_indent: -1,
+		};
+		for line in self.lines[..split.min(self.lines.len())].iter().rev() {
+			measure.pre_indent = indent(line);
+			if measure.pre_indent != -1 {
+				break;
+			}
+			measure.pre_blank += 1;
+			if measure.pre_blank == MAX_BLANKS {
+				measure.pre_indent = 0;
+				break;
+			}
+		}
+		for line in self.lines.iter().skip(split + 1) {
+			measure.post_indent = indent(line);
+			if measure.post_indent != -1 {
+				break;
+			}
+			measure.post_blank += 1;
+			if measure.post_blank == MAX_BLANKS {
+				measure.post_indent = 0;
+				break;
+			}
+		}
+		measure
+	}
+}
+
+/// Surroundings of a position between two lines
+struct SplitMeasurement {
+	end_of_file: bool,
+	/// Indentation of line after split, -1 if it is blank or missing
+	indent: i32,
+	/// Number of blank lines before split
+	pre_blank: i32,
+	/// Indentation of nearest non-blank line before split, -1 if none
+	pre_indent: i32,
+	/// Number of blank lines after the line following split
+	post_blank: i32,
+	/// Indentation of nearest non-blank line after the line following split, -1 if none
+	post_indent: i32,
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct SplitScore {
+	effective_indent: i32,
+	penalty: i32,
+}
+
+impl SplitScore {
+	fn add(&mut self, measure: &SplitMeasurement) {
+		if measure.pre_indent == -1 && measure.pre_blank == 0 {
+			self.penalty += START_OF_FILE_PENALTY;
+		}
+		if measure.end_of_file {
+			self.penalty += END_OF_FILE_PENALTY;
+		}
+		let post_blank = if measure.indent == -1 { 1 + measure.post_blank } else { 0 };
+		let total_blank = measure.pre_blank + post_blank;
+		self.penalty += TOTAL_BLANK_WEIGHT * total_blank + POST_BLANK_WEIGHT * post_blank;
+		let indent = if measure.indent != -1 { measure.indent } else { measure.post_indent };
+		let any_blanks = total_blank != 0;
+		self.effective_indent += indent;
+		if indent == -1 || measure.pre_indent == -1 || indent == measure.pre_indent {
+			return;
+		}
+		self.penalty += if indent > measure.pre_indent {
+			if any_blanks { RELATIVE_INDENT_WITH_BLANK_PENALTY } else { RELATIVE_INDENT_PENALTY }
+		} else if measure.post_indent != -1 && measure.post_indent > indent {
+			if any_blanks { RELATIVE_OUTDENT_WITH_BLANK_PENALTY } else { RELATIVE_OUTDENT_PENALTY }
+		} else if any_blanks {
+			RELATIVE_DEDENT_WITH_BLANK_PENALTY
+		} else {
+			RELATIVE_DEDENT_PENALTY
+		};
+	}
+
+	/// Negative if `self` is better than `other`
+	fn cmp(&self, other: &Self) -> i32 {
+		INDENT_WEIGHT * (self.effective_indent.cmp(&other.effective_indent) as i32) + self.penalty - other.penalty
+	}
+}
+
+/// Width of leading whitespace with tabs to multiple of 8, -1 for blank line
+fn indent(line: &str) -> i32 {
+	let mut width = 0;
+	for ch in line.bytes() {
+		if !ch.is_ascii_whitespace() && ch != b'\x0b' {
+			return width;
+		}
+		match ch {
+			b' ' => width += 1,
+			b'\t' => width += 8 - width % 8,
+			_ => {}
+		}
+		if width >= MAX_INDENT {
+			return MAX_INDENT;
+		}
+	}
+	-1
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::git::algorithm::{diff_lines, DiffAlgorithm, Edit};
+	use crate::git::slider::indent;
+
+	/// Unified diff lines of whole files
+	fn render(old: &str, new: &str) -> String {
+		let old = old.split_inclusive('\n').collect::<Vec<_>>();
+		let new = new.split_inclusive('\n').collect::<Vec<_>>();
+		let (mut o, mut n) = (0, 0);
+		let mut diff = String::new();
+		for edit in diff_lines(&old, &new, DiffAlgorithm::Myers) {
+			let (prefix, line) = match edit {
+				Edit::Equal => {
+					(o, n) = (o + 1, n + 1);
+					(' ', old[o - 1])
+				}
+				Edit::Delete => {
+					o += 1;
+					('-', old[o - 1])
+				}
+				Edit::Insert => {
+					n += 1;
+					('+', new[n - 1])
+				}
+			};
+			diff.push(prefix);
+			diff.push_str(line);
+		}
+		diff
+	}
+
+	#[test]
+	fn test_indent() {
+		assert_eq!(indent("fn main() {\n"), 0);
+		assert_eq!(indent("  \tlet a;\n"), 8);
+		assert_eq!(indent("    }\n"), 4);
+		assert_eq!(indent(" \t \n"), -1);
+	}
+
+	#[test]
+	fn test_slide_to_block_boundary() {
+		let old = "fn a() {\n}\n\nfn c() {\n}\n";
+		let new = "fn a() {\n}\n\nfn b() {\n}\n\nfn c() {\n}\n";
+		// without heuristic the new function would start with `}` of the previous one
+		assert_eq!(render(old, new), " fn a() {\n }\n \n+fn b() {\n+}\n+\n fn c() {\n }\n");
+	}
+
+	#[test]
+	fn test_slide_next_to_change() {
+		let old = "a\nx\nb\nx\n";
+		let new = "a\nx\nc\nx\n";
+		assert_eq!(render(old, new), " a\n x\n-b\n+c\n x\n");
+	}
+}
@@ -0,0 +1,97 @@
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+
+/// Point in time with time zone as recorded by git
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize)]
+pub struct GitTime {
+	/// Seconds since unix epoch
+	pub seconds: i64,
+	/// Offset from UTC in minutes
+	pub offset: i32,
+}
+
+impl GitTime {
+	/// Parse raw git date `<seconds> <+|-hhmm>` (`--date=raw` or commit header)
+	pub fn parse_raw(raw: &str) -> Option<Self> {
+		let (seconds, zone) = raw.trim().split_once(' ')?;
+		let seconds = seconds.parse().ok()?;
+		let (sign, zone) = match zone.as_bytes().first()? {
+			b'+' => (1, &zone[1..]),
+			b'-' => (-1, &zone[1..]),
+			_ => return None,
+		};
+		if zone.len() != 4 {
+			return None;
+		}
+		let hours = zone[..2].parse::<i32>().ok()?;
+		let minutes = zone[2..].parse::<i32>().ok()?;
+		Some(Self {
+			seconds,
+			offset: sign * (hours * 60 + minutes),
+		})
+	}
+
+	/// Date in local time zone as `(year, month, day)`
+	pub fn date(&self) -> (i64, u32, u32) {
+		civil_from_days(self.local_seconds().div_euclid(86400))
+	}
+
+	/// Time in local time zone as `(hour, minute, second)`
+	pub fn time(&self) -> (u32, u32, u32) {
+		let secs = self.local_seconds().rem_euclid(86400) as u32;
+		(secs / 3600, secs / 60 % 60, secs % 60)
+	}
+
+	fn local_seconds(&self) -> i64 {
+		self.seconds + self.offset as i64 * 60
+	}
+}
+
+/// Format as ISO 8601 like `2023-01-02 15:04:05 +0700`
+impl Display for GitTime {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let (year, month, day) = self.date();
+		let (hour, minute, second) = self.time();
+		let sign = if self.offset < 0 { '-' } else { '+' };
+		let offset = self.offset.abs();
+		write!(
+			f,
+			"{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02} {sign}{:02}{:02}",
+			offset / 60,
+			offset % 60
+		)
+	}
+}
+
+/// Convert days since unix epoch to gregorian date (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+	let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+	(year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::git::GitTime;
+
+	#[test]
+	fn test_parse_time() {
+		let time = GitTime::parse_raw("1697000000 +0700").unwrap();
+		assert_eq!(time.offset, 420);
+		assert_eq!(time.to_string(), "2023-10-11 11:53:20 +0700");
+
+		let time = GitTime::parse_raw("0 -0130").unwrap();
+		assert_eq!(time.to_string(), "1969-12-31 22:30:00 -0130");
+
+		assert!(GitTime::parse_raw("1697000000").is_none());
+		assert!(GitTime::parse_raw("abc +0000").is_none());
+	}
+}
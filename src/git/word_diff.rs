@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+use crate::git::algorithm::{diff_lines, DiffAlgorithm, Edit};
+use crate::git::DiffType;
+
+/// Word level comparison of a removed line and the added line replacing it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WordDiff<'a> {
+	/// Line content in order, `DiffType::None` for unchanged text
+	pub segments: Vec<(DiffType, &'a str)>,
+}
+
+impl<'a> WordDiff<'a> {
+	/// Compare line contents (without `-`/`+` prefix),
+	/// `None` if lines have too little in common to be shown as one line
+	pub fn new(old: &'a str, new: &'a str) -> Option<Self> {
+		let old_tokens = tokenize(old);
+		let new_tokens = tokenize(new);
+		let edits = diff_lines(&old_tokens, &new_tokens, DiffAlgorithm::Myers);
+		let (mut o, mut n) = (0, 0);
+		let mut kept = 0;
+		let mut segments: Vec<(DiffType, &'a str)> = Vec::new();
+		for edit in edits {
+			let (typ, token) = match edit {
+				Edit::Equal => {
+					o += 1;
+					n += 1;
+					if !old_tokens[o - 1].trim().is_empty() {
+						kept += old_tokens[o - 1].len();
+					}
+					(DiffType::None, old_tokens[o - 1])
+				}
+				Edit::Delete => {
+					o += 1;
+					(DiffType::Remove, old_tokens[o - 1])
+				}
+				Edit::Insert => {
+					n += 1;
+					(DiffType::Add, new_tokens[n - 1])
+				}
+			};
+			push_segment(&mut segments, typ, token, old, new);
+		}
+		// unchanged text must be at least a third of the longer line
+		let longest = old.trim().len().max(new.trim().len());
+		if kept * 3 < longest {
+			return None;
+		}
+		Some(Self { segments })
+	}
+
+	/// Byte ranges of removed text in old line
+	pub fn old_spans(&self) -> Vec<Range<usize>> {
+		self.spans(DiffType::Remove)
+	}
+
+	/// Byte ranges of added text in new line
+	pub fn new_spans(&self) -> Vec<Range<usize>> {
+		self.spans(DiffType::Add)
+	}
+
+	fn spans(&self, changed: DiffType) -> Vec<Range<usize>> {
+		let mut spans = Vec::new();
+		let mut at = 0;
+		for (typ, text) in &self.segments {
+			if *typ == changed {
+				spans.push(at..at + text.len());
+			}
+			if *typ == changed || *typ == DiffType::None {
+				at += text.len();
+			}
+		}
+		spans
+	}
+}
+
+/// Join token with previous segment of same type, tokens are adjacent slices of `old` or `new`
+fn push_segment<'a>(segments: &mut Vec<(DiffType, &'a str)>, typ: DiffType, token: &'a str, old: &'a str, new: &'a str) {
+	if let Some((last, text)) = segments.last_mut() {
+		if *last == typ {
+			let line = if typ == DiffType::Add { new } else { old };
+			*text = join(line, text, token);
+			return;
+		}
+	}
+	segments.push((typ, token));
+}
+
+/// Extend `head` to the end of `tail` if both are adjacent slices of `line`
+fn join<'a>(line: &'a str, head: &'a str, tail: &'a str) -> &'a str {
+	let base = line.as_ptr() as usize;
+	let start = head.as_ptr() as usize - base;
+	let end = tail.as_ptr() as usize - base + tail.len();
+	if end - start == head.len() + tail.len() {
+		&line[start..end]
+	} else {
+		head
+	}
+}
+
+/// Split line into words, whitespace runs and single punctuation characters
+fn tokenize(line: &str) -> Vec<&str> {
+	let class = |ch: char| if ch.is_alphanumeric() || ch == '_' {
+		0
+	} else if ch.is_whitespace() {
+		1
+	} else {
+		2
+	};
+	let mut tokens = Vec::new();
+	let mut start = 0;
+	let mut chars = line.char_indices().peekable();
+	while let Some((_, ch)) = chars.next() {
+		let end = chars.peek().map(|it| it.0).unwrap_or(line.len());
+		let next = chars.peek().map(|it| class(it.1));
+		if class(ch) == 2 || next != Some(class(ch)) {
+			tokens.push(&line[start..end]);
+			start = end;
+		}
+	}
+	tokens
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::git::word_diff::{tokenize, WordDiff};
+	use crate::git::DiffType;
+
+	#[test]
+	fn test_tokenize() {
+		assert_eq!(tokenize("let a_b = foo(1);"), vec!["let", " ", "a_b", " ", "=", " ", "foo", "(", "1", ")", ";"]);
+		assert!(tokenize("").is_empty());
+	}
+
+	#[test]
+	fn test_word_diff() {
+		let diff = WordDiff::new("    let total = price * count;", "    let total = price * amount;").unwrap();
+		assert_eq!(diff.segments, vec![
+			(DiffType::None, "    let total = price * "),
+			(DiffType::Remove, "count"),
+			(DiffType::Add, "amount"),
+			(DiffType::None, ";"),
+		]);
+		assert_eq!(diff.old_spans(), vec![24..29]);
+		assert_eq!(diff.new_spans(), vec![24..30]);
+
+		assert!(WordDiff::new("fn main() {", "struct Point { x: i32 }").is_none());
+	}
+}
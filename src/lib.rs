@@ -0,0 +1,5 @@
+pub mod config;
+pub mod error;
+pub mod generator;
+pub mod git;
+pub mod util;
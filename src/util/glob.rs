@@ -0,0 +1,126 @@
+/// Minimal path glob supporting `*`, `**`, `?` and `[...]` character classes.
+///
+/// Pattern without `/` match file name at any depth (like `.gitignore`),
+/// otherwise it match whole path relative to repository root.
+#[derive(Debug, Clone)]
+pub struct Glob {
+	pattern: String,
+	basename: bool,
+}
+
+impl Glob {
+	pub fn new(pattern: impl Into<String>) -> Self {
+		let pattern = pattern.into();
+		let trimmed = pattern.trim_start_matches('/');
+		let basename = !pattern.contains('/');
+		Self {
+			pattern: trimmed.to_string(),
+			basename,
+		}
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.pattern
+	}
+
+	pub fn matches(&self, path: &str) -> bool {
+		let path = path.trim_start_matches('/');
+		if self.basename {
+			let name = path.rsplit('/').next().unwrap_or(path);
+			glob_match(self.pattern.as_bytes(), name.as_bytes())
+		} else {
+			glob_match(self.pattern.as_bytes(), path.as_bytes())
+		}
+	}
+}
+
+/// Return `true` if any glob in `globs` matches `path`
+pub fn matches_any(globs: &[Glob], path: &str) -> bool {
+	globs.iter().any(|it| it.matches(path))
+}
+
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+	match pattern.first() {
+		None => text.is_empty(),
+		Some(b'*') if pattern.get(1) == Some(&b'*') => {
+			let rest = &pattern[2..];
+			match rest.strip_prefix(b"/") {
+				// `**/` match zero or more directories
+				Some(rest) => glob_match(rest, text)
+					|| text.iter().enumerate().any(|(i, c)| *c == b'/' && glob_match(rest, &text[i + 1..])),
+				None => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+			}
+		}
+		Some(b'*') => {
+			let rest = &pattern[1..];
+			for i in 0..=text.len() {
+				if glob_match(rest, &text[i..]) {
+					return true;
+				}
+				if text.get(i) == Some(&b'/') {
+					break;
+				}
+			}
+			false
+		}
+		Some(b'?') => matches!(text.first(), Some(c) if *c != b'/') && glob_match(&pattern[1..], &text[1..]),
+		Some(b'[') => {
+			let Some(end) = pattern.iter().skip(1).position(|it| *it == b']') else {
+				return text.first() == Some(&b'[') && glob_match(&pattern[1..], &text[1..]);
+			};
+			let Some(c) = text.first() else { return false };
+			let class = &pattern[1..end + 1];
+			let (negate, class) = match class.first() {
+				Some(b'!') | Some(b'^') => (true, &class[1..]),
+				_ => (false, class),
+			};
+			let mut found = false;
+			let mut i = 0;
+			while i < class.len() {
+				if i + 2 < class.len() && class[i + 1] == b'-' {
+					found |= (class[i]..=class[i + 2]).contains(c);
+					i += 3;
+				} else {
+					found |= class[i] == *c;
+					i += 1;
+				}
+			}
+			found != negate && glob_match(&pattern[end + 2..], &text[1..])
+		}
+		Some(p) => text.first() == Some(p) && glob_match(&pattern[1..], &text[1..]),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::util::glob::Glob;
+
+	#[test]
+	fn test_basename_glob() {
+		let glob = Glob::new("*.png");
+		assert!(glob.matches("logo.png"));
+		assert!(glob.matches("assets/img/logo.png"));
+		assert!(!glob.matches("logo.png.txt"));
+	}
+
+	#[test]
+	fn test_path_glob() {
+		let glob = Glob::new("src/*.rs");
+		assert!(glob.matches("src/main.rs"));
+		assert!(!glob.matches("src/git/diff.rs"));
+
+		let glob = Glob::new("src/**/*.rs");
+		assert!(glob.matches("src/main.rs"));
+		assert!(glob.matches("src/git/diff.rs"));
+		assert!(!glob.matches("tests/main.rs"));
+
+		let glob = Glob::new("docs/**/intro.md");
+		assert!(glob.matches("docs/intro.md"));
+		assert!(glob.matches("docs/a/b/intro.md"));
+		assert!(!glob.matches("docs/a/xintro.md"));
+
+		let glob = Glob::new("/Cargo.[lt]o?k");
+		assert!(glob.matches("Cargo.lock"));
+		assert!(!glob.matches("Cargo.toml"));
+	}
+}
@@ -1,9 +1,24 @@
+/// Read a line, end the stream when reader reach EOF
 #[macro_export]
 macro_rules! read_or_none {
     ($self:ident, $line:ident) => {
 		if 0 == $self.inner.read_line(&mut $line).await? {
 			tracing::trace!("EOF");
-			return Ok(None);
+			return $self.finish().await;
+		}
+		if $line.ends_with(|it:char| it.is_whitespace()) {
+	        let _ = $line.pop();
+		}
+    };
+}
+
+/// Read a line, EOF is an error because entry is not complete
+#[macro_export]
+macro_rules! read_or_eof {
+    ($self:ident, $line:ident) => {
+		if 0 == $self.inner.read_line(&mut $line).await? {
+			$self.finish::<()>().await?;
+			return Err($self.error($crate::error::ParseErrorKind::UnexpectedEof, "").into());
 		}
 		if $line.ends_with(|it:char| it.is_whitespace()) {
 	        let _ = $line.pop();
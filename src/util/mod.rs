@@ -2,4 +2,5 @@ pub mod proc;
 pub mod iter;
 pub mod macros;
 pub mod peekable_reader;
-pub mod string;
\ No newline at end of file
+pub mod string;
+pub mod glob;
\ No newline at end of file
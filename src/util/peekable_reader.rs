@@ -1,14 +1,13 @@
 use std::io;
-use std::ops::Deref;
-use std::pin::Pin;
-use std::task::{Context, Poll};
 
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader, ReadBuf};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 
 pub struct PeekableLine<R> {
 	inner: BufReader<R>,
 	buffer: String,
 	has_next: bool,
+	/// number of consumed lines
+	line: usize,
 }
 
 // impl<T> Deref for PeekableLine<T> {
@@ -25,19 +24,33 @@ impl<T: AsyncRead + Unpin> PeekableLine<T> {
 			inner,
 			has_next: false,
 			buffer: String::with_capacity(128),
+			line: 0,
 		}
 	}
 
+	/// Number of consumed lines, peeked line is `line() + 1`
+	pub fn line(&self) -> usize {
+		self.line
+	}
+
 	pub async fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
-		Ok(if !self.has_next {
+		let len = if !self.has_next {
 			self.inner.read_line(buf).await?
 		} else {
+			self.has_next = false;
 			buf.push_str(&self.buffer);
 			self.buffer.len()
-		})
+		};
+		if len > 0 {
+			self.line += 1;
+		}
+		Ok(len)
 	}
 
 	pub fn consume_peek(&mut self) {
+		if self.has_next {
+			self.line += 1;
+		}
 		self.has_next = false;
 	}
 
@@ -56,6 +69,7 @@ impl<T: AsyncRead + Unpin> PeekableLine<T> {
 			if len == 0 {
 				return Ok("");
 			}
+			self.has_next = true;
 			Ok(&self.buffer)
 		}
 	}
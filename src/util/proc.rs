@@ -9,6 +9,8 @@ use tokio::io::AsyncReadExt;
 use tokio::process::{Child, Command};
 use tracing::warn;
 
+use crate::error::StepdocsError;
+
 pub enum RawOutputMessage {
 	Success(Vec<u8>),
 	Error(Vec<u8>),
@@ -53,6 +55,29 @@ pub enum OutputMessage {
 	Error(String),
 }
 
+/// Wait for child to exit, fail with its stderr if exit status is not success
+pub async fn check_exit(child: &mut Child, command: impl Into<String>) -> Result<(), StepdocsError> {
+	let stderr = child.stderr.take();
+	// read stderr while waiting, child may block on full pipe
+	let read = async {
+		let mut message = Vec::new();
+		if let Some(mut stderr) = stderr {
+			stderr.read_to_end(&mut message).await?;
+		}
+		io::Result::Ok(message)
+	};
+	let (status, message) = tokio::join!(child.wait(), read);
+	let (status, message) = (status?, message?);
+	if status.success() {
+		return Ok(());
+	}
+	let message = String::from_utf8_lossy(&message).trim().to_string();
+	Err(StepdocsError::Git {
+		command: command.into(),
+		message: if message.is_empty() { status.to_string() } else { message },
+	})
+}
+
 pub fn spawn(cmd: impl AsRef<OsStr>, args: impl IntoIterator<Item=impl AsRef<OsStr>>, cwd: impl AsRef<Path>) -> io::Result<Child> {
 	Command::new(cmd)
 		.kill_on_drop(true)
@@ -65,19 +90,32 @@ pub fn spawn(cmd: impl AsRef<OsStr>, args: impl IntoIterator<Item=impl AsRef<OsS
 }
 
 pub async fn run_process(cmd: impl AsRef<OsStr>, args: impl IntoIterator<Item=impl AsRef<OsStr>>, cwd: impl AsRef<Path>) -> io::Result<RawOutputMessage> {
-	let mut child = spawn(cmd, args, cwd)?;
-	let status = child.wait().await?;
+	let child = spawn(cmd, args, cwd)?;
+	// read output while waiting, child may block on full pipe
+	let output = child.wait_with_output().await?;
 
-	if status.success() {
-		let mut buffer = Vec::new();
-		let mut out = child.stdout.take().unwrap();
-		out.read_to_end(&mut buffer).await?;
-		Ok(RawOutputMessage::Success(buffer))
+	if output.status.success() {
+		Ok(RawOutputMessage::Success(output.stdout))
 	} else {
-		let mut buffer = Vec::new();
-		let mut out = child.stderr.take().unwrap();
-		out.read_to_end(&mut buffer).await?;
-		warn!("Child error: {}", BStr::new(&buffer));
-		Ok(RawOutputMessage::Error(buffer))
+		warn!("Child error: {}", BStr::new(&output.stderr));
+		Ok(RawOutputMessage::Error(output.stderr))
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use crate::error::StepdocsError;
+	use crate::util::proc::{check_exit, spawn};
+
+	#[tokio::test]
+	async fn test_check_exit() {
+		// more stderr than a pipe buffer hold
+		let mut child = spawn("sh", ["-c", "head -c 200000 /dev/zero | tr '\\0' x >&2; exit 3"], ".").unwrap();
+		let err = check_exit(&mut child, "test").await.unwrap_err();
+		let StepdocsError::Git { message, .. } = err else { panic!("{err:?}") };
+		assert_eq!(message.len(), 200000);
+
+		let mut child = spawn("sh", ["-c", "exit 0"], ".").unwrap();
+		assert!(check_exit(&mut child, "test").await.is_ok());
+	}
+}